@@ -1,5 +1,9 @@
+#[macro_use]
 extern crate glium;
 
+pub mod font;
+pub mod text;
+
 #[derive(Debug, Clone)]
 pub struct DataBuilder<'s, V, U>
     where V: glium::Vertex + Copy,
@@ -31,6 +35,7 @@ impl<'s, V, U> DataBuilder<'s, V, U>
             vertex_buf: try!(glium::VertexBuffer::new(display, &self.vertices)),
             index_buf: try!(glium::IndexBuffer::new(display, primitive_type, &self.indices)),
             instance_buf: try!(glium::VertexBuffer::empty_persistent(display, max_instances)),
+            num_instances: 0,
             uniforms: self.uniforms.into_uniforms(),
             program: try!(glium::Program::from_source(display, self.vshader_src, self.fshader_src, None)),
         })
@@ -45,6 +50,7 @@ pub struct InstancedData<V, I, U>
     pub vertex_buf: glium::VertexBuffer<V>,
     pub index_buf: glium::IndexBuffer<u16>,
     pub instance_buf: glium::VertexBuffer<I>,
+    pub num_instances: usize,
     pub uniforms: U,
     pub program: glium::Program,
 }
@@ -63,6 +69,8 @@ impl<V, I, U> InstancedData<V, I, U>
             return Err(MaxInstancesExceeded);
         }
 
+        self.num_instances = iter.len();
+
         for (dest, src) in self.instance_buf.map().iter_mut().zip(iter) {
             *dest = src;
         }
@@ -70,13 +78,25 @@ impl<V, I, U> InstancedData<V, I, U>
         Ok(())
     }
 
+    /// The maximum number of instances that can be drawn.
+    pub fn max_instances(&self) -> usize {
+        self.instance_buf.len()
+    }
+
+    /// Draw the instances from the last call to `update_instances`.
     pub fn draw<S: glium::Surface>(
         &self,
         surface: &mut S,
         params: &glium::DrawParameters)
         -> Result<(), glium::DrawError> {
 
-        surface.draw((&self.vertex_buf, self.instance_buf.per_instance().unwrap()),
+        if self.num_instances == 0 {
+            return Ok(());
+        }
+
+        let instances = self.instance_buf.slice(0..self.num_instances).unwrap();
+
+        surface.draw((&self.vertex_buf, instances.per_instance().unwrap()),
                      &self.index_buf,
                      &self.program,
                      &self.uniforms,
@@ -84,6 +104,86 @@ impl<V, I, U> InstancedData<V, I, U>
     }
 }
 
+/// Vertex data for a single non-instanced draw call, whose vertices are
+/// replaced every frame. Uniforms are provided when drawing, so they can borrow
+/// textures.
+pub struct DynamicData<V>
+    where V: Copy {
+
+    pub vertex_buf: glium::VertexBuffer<V>,
+    pub num_vertices: usize,
+    pub primitive_type: glium::index::PrimitiveType,
+    pub program: glium::Program,
+}
+
+impl<V> DynamicData<V>
+    where V: glium::Vertex + Copy {
+
+    /// Create dynamic data with room for the given number of vertices.
+    pub fn new<F>(
+        display: &F,
+        max_vertices: usize,
+        primitive_type: glium::index::PrimitiveType,
+        vshader_src: &str,
+        fshader_src: &str)
+        -> Result<Self, Error>
+
+        where F: glium::backend::Facade {
+
+        Ok(DynamicData {
+            vertex_buf: try!(glium::VertexBuffer::empty_dynamic(display, max_vertices)),
+            num_vertices: 0,
+            primitive_type: primitive_type,
+            program: try!(glium::Program::from_source(display, vshader_src, fshader_src, None)),
+        })
+    }
+
+    /// The maximum number of vertices that can be drawn.
+    pub fn max_vertices(&self) -> usize {
+        self.vertex_buf.len()
+    }
+
+    pub fn update_vertices<Vertices, Iter>(&mut self, vertices: Vertices) -> Result<(), MaxInstancesExceeded>
+        where Vertices: IntoIterator<Item = V, IntoIter = Iter>, Iter: ExactSizeIterator<Item = V>
+    {
+        let iter = vertices.into_iter();
+
+        if self.vertex_buf.len() < iter.len() {
+            return Err(MaxInstancesExceeded);
+        }
+
+        self.num_vertices = iter.len();
+
+        for (dest, src) in self.vertex_buf.map().iter_mut().zip(iter) {
+            *dest = src;
+        }
+
+        Ok(())
+    }
+
+    /// Draw the vertices from the last call to `update_vertices`.
+    pub fn draw<S, U>(
+        &self,
+        surface: &mut S,
+        uniforms: &U,
+        params: &glium::DrawParameters)
+        -> Result<(), glium::DrawError>
+
+        where S: glium::Surface,
+              U: glium::uniforms::Uniforms {
+
+        if self.num_vertices == 0 {
+            return Ok(());
+        }
+
+        surface.draw(self.vertex_buf.slice(0..self.num_vertices).unwrap(),
+                     &glium::index::NoIndices(self.primitive_type),
+                     &self.program,
+                     uniforms,
+                     params)
+    }
+}
+
 pub trait ToVertex {
     type ToVertex: glium::Vertex + Copy;
 
@@ -124,6 +224,7 @@ pub enum Error {
     VertexBufferCreationError(glium::vertex::BufferCreationError),
     IndexBufferCreationError(glium::index::BufferCreationError),
     ProgramCreationError(glium::program::ProgramCreationError),
+    TextureCreationError(glium::texture::TextureCreationError),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -141,6 +242,12 @@ impl From<glium::index::BufferCreationError> for Error {
     }
 }
 
+impl From<glium::texture::TextureCreationError> for Error {
+    fn from(err: glium::texture::TextureCreationError) -> Error {
+        Error::TextureCreationError(err)
+    }
+}
+
 impl From<glium::program::ProgramCreationError> for Error {
     fn from(err: glium::program::ProgramCreationError) -> Error {
         Error::ProgramCreationError(err)
@@ -0,0 +1,182 @@
+//! Drawing text in window coordinates, using a glyph atlas built from the
+//! embedded bitmap font.
+
+use glium;
+use glium::backend::Facade;
+use glium::index::PrimitiveType;
+use glium::texture::{RawImage2d, Texture2d};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+use font::*;
+use super::{DynamicData, Error};
+
+/// The width of each cell in the atlas, including a column of spacing.
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+
+/// The height of each line of text, including spacing, in font pixels.
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/// The number of cells in the atlas. There is one for each glyph, followed by
+/// one which is completely filled, for drawing solid rectangles.
+const NUM_CELLS: u32 = 96;
+
+const SOLID_CELL: u32 = NUM_CELLS - 1;
+
+#[derive(Debug, Clone, Copy)]
+struct TextVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+implement_vertex!(TextVertex, position, tex_coords, color);
+
+/// Queues up text and rectangles to be drawn on top of everything else in a
+/// frame. Positions are in pixels from the top left of the window.
+pub struct TextRenderer {
+    atlas: Texture2d,
+    data: DynamicData<TextVertex>,
+    queued: Vec<TextVertex>,
+}
+
+impl TextRenderer {
+    /// Create a text renderer with room for the given number of characters
+    /// per frame. Any more are not drawn.
+    pub fn new<F>(display: &F, max_chars: usize) -> Result<Self, Error>
+        where F: Facade
+    {
+        Ok(TextRenderer {
+            atlas: try!(Texture2d::new(display, build_atlas())),
+            data: try!(DynamicData::new(display,
+                                        max_chars * 6,
+                                        PrimitiveType::TrianglesList,
+                                        include_str!("text_vshader.glsl"),
+                                        include_str!("text_fshader.glsl"))),
+            queued: Vec::new(),
+        })
+    }
+
+    /// The width in pixels of the given text when drawn at the given scale.
+    /// For multi-line text, this is the width of the longest line.
+    pub fn text_width(text: &str, scale: f32) -> f32 {
+        let longest = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        (longest as u32 * CELL_WIDTH) as f32 * scale
+    }
+
+    /// Queue some text with its top left corner at the given position. Each
+    /// font pixel is drawn `scale` pixels across. Characters outside printable
+    /// ASCII are drawn as `?`.
+    pub fn queue_text(&mut self, text: &str, pos: (f32, f32), scale: f32, color: [f32; 4]) {
+        let (mut x, mut y) = pos;
+
+        for c in text.chars() {
+            if c == '\n' {
+                x = pos.0;
+                y += LINE_HEIGHT as f32 * scale;
+                continue;
+            }
+
+            let cell = if FIRST_CHAR <= c && c <= '~' {
+                c as u32 - FIRST_CHAR as u32
+            } else {
+                '?' as u32 - FIRST_CHAR as u32
+            };
+
+            let size = (GLYPH_WIDTH as f32 * scale, GLYPH_HEIGHT as f32 * scale);
+            self.queue_cell(cell, (x, y), size, color);
+
+            x += CELL_WIDTH as f32 * scale;
+        }
+    }
+
+    /// Queue a solid rectangle with its top left corner at the given position.
+    pub fn queue_rect(&mut self, pos: (f32, f32), size: (f32, f32), color: [f32; 4]) {
+        self.queue_cell(SOLID_CELL, pos, size, color);
+    }
+
+    /// Draw everything queued since the last draw, then clear the queue.
+    pub fn draw<S>(&mut self,
+                   surface: &mut S,
+                   params: &glium::DrawParameters)
+                   -> Result<(), glium::DrawError>
+        where S: glium::Surface
+    {
+        let max_vertices = self.data.max_vertices();
+        self.data.update_vertices(self.queued.drain(..).take(max_vertices).collect::<Vec<_>>())
+            .unwrap();
+
+        // Map pixels from the top left to OpenGL's coordinates.
+        let (width, height) = surface.get_dimensions();
+        let matrix = [[2.0 / width as f32, 0.0, 0.0, 0.0],
+                      [0.0, -2.0 / height as f32, 0.0, 0.0],
+                      [0.0, 0.0, 1.0, 0.0],
+                      [-1.0, 1.0, 0.0, 1.0f32]];
+
+        let uniforms = uniform! {
+            matrix: matrix,
+            atlas: self.atlas
+                .sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
+        };
+
+        self.data.draw(surface, &uniforms, params)
+    }
+
+    fn queue_cell(&mut self, cell: u32, pos: (f32, f32), size: (f32, f32), color: [f32; 4]) {
+        let atlas_width = (NUM_CELLS * CELL_WIDTH) as f32;
+
+        // The atlas is stored bottom row first, so the top of each glyph is at
+        // the top of the texture.
+        let u0 = (cell * CELL_WIDTH) as f32 / atlas_width;
+        let u1 = (cell * CELL_WIDTH + GLYPH_WIDTH) as f32 / atlas_width;
+        let v0 = 1.0;
+        let v1 = 1.0 - GLYPH_HEIGHT as f32 / LINE_HEIGHT as f32;
+
+        let (x0, y0) = pos;
+        let (x1, y1) = (pos.0 + size.0, pos.1 + size.1);
+
+        let vertex = |x, y, u, v| {
+            TextVertex {
+                position: [x, y],
+                tex_coords: [u, v],
+                color: color,
+            }
+        };
+
+        self.queued.extend_from_slice(&[vertex(x0, y0, u0, v0),
+                                         vertex(x1, y0, u1, v0),
+                                         vertex(x0, y1, u0, v1),
+                                         vertex(x1, y0, u1, v0),
+                                         vertex(x1, y1, u1, v1),
+                                         vertex(x0, y1, u0, v1)]);
+    }
+}
+
+/// Build the glyph atlas: one row of cells, with opaque white where the font
+/// has pixels and transparent elsewhere.
+fn build_atlas() -> RawImage2d<'static, u8> {
+    let width = NUM_CELLS * CELL_WIDTH;
+    let height = LINE_HEIGHT;
+
+    let mut data = vec![0u8; (width * height * 4) as usize];
+
+    for cell in 0..NUM_CELLS {
+        for row in 0..GLYPH_HEIGHT {
+            for col in 0..GLYPH_WIDTH {
+                let bit = 1 << (GLYPH_WIDTH - 1 - col);
+                let filled = cell == SOLID_CELL || GLYPHS[cell as usize][row as usize] & bit != 0;
+
+                if filled {
+                    let i = ((row * width + cell * CELL_WIDTH + col) * 4) as usize;
+                    for byte in &mut data[i..i + 4] {
+                        *byte = 255;
+                    }
+                }
+            }
+        }
+    }
+
+    // The data was built top row first.
+    RawImage2d::from_raw_rgba_reversed(data, (width, height))
+}
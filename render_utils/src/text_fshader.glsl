@@ -0,0 +1,16 @@
+#version 140
+
+uniform sampler2D atlas;
+in vec2 v_tex_coords;
+in vec4 v_color;
+out vec4 f_color;
+
+void main() {
+  vec4 glyph = texture(atlas, v_tex_coords);
+
+  if (glyph.a < 0.5) {
+    discard;
+  }
+
+  f_color = v_color * glyph;
+}
@@ -0,0 +1,13 @@
+#version 140
+
+uniform mat4 global_matrix;
+in vec2 position;
+in vec4 color;
+
+out vec4 v_color;
+
+void main() {
+  v_color = color;
+
+  gl_Position = global_matrix * vec4(position, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+extern crate battlebots_support;
+
+use battlebots_support::bots::duelist::Duelist;
+use battlebots_support::robo_controller::*;
+
+fn main() {
+    run(&mut Duelist::new()).unwrap();
+}
@@ -0,0 +1,8 @@
+extern crate battlebots_support;
+
+use battlebots_support::bots::linear_gunner::LinearGunner;
+use battlebots_support::robo_controller::*;
+
+fn main() {
+    run(&mut LinearGunner::new()).unwrap();
+}
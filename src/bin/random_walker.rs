@@ -0,0 +1,8 @@
+extern crate battlebots_support;
+
+use battlebots_support::bots::random_walker::RandomWalker;
+use battlebots_support::robo_controller::*;
+
+fn main() {
+    run(&mut RandomWalker::new()).unwrap();
+}
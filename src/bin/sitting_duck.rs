@@ -0,0 +1,8 @@
+extern crate battlebots_support;
+
+use battlebots_support::bots::sitting_duck::SittingDuck;
+use battlebots_support::robo_controller::*;
+
+fn main() {
+    run(&mut SittingDuck).unwrap();
+}
@@ -0,0 +1,8 @@
+extern crate battlebots_support;
+
+use battlebots_support::bots::wall_hugger::WallHugger;
+use battlebots_support::robo_controller::*;
+
+fn main() {
+    run(&mut WallHugger::new()).unwrap();
+}
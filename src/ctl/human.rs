@@ -0,0 +1,171 @@
+//! A robot controlled by a person at the keyboard, so that developers can spar
+//! against their own robots. W and S thrust forwards and backwards, A and D
+//! turn, the left and right arrow keys turn the gun, or else it follows the
+//! mouse, and clicking shoots.
+
+use math::*;
+use config::Config;
+use rpc::*;
+use targeting::{angle_between, bearing, predict};
+use ctl::user::{Brain, Ctl};
+
+use glium::glutin::{Event, ElementState, MouseButton, VirtualKeyCode};
+
+use std::sync::{Arc, Mutex};
+
+/// The program name given in a match's header for keyboard-controlled robots.
+pub const PROGRAM: &'static str = "human";
+
+/// The keys and buttons currently held, shared between the window, which
+/// updates it, and the robot's brain, which reads it on every step.
+#[derive(Debug, Clone, Default)]
+pub struct Keyboard {
+    keys: Arc<Mutex<Keys>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Keys {
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    gun_left: bool,
+    gun_right: bool,
+
+    /// Where the mouse is in the world, for the gun to point at.
+    aim: Option<Vector2>,
+
+    /// Whether the mouse button is held, and whether it has been clicked since
+    /// the last step. A click between steps still fires a shot.
+    shooting: bool,
+    clicked: bool,
+}
+
+/// The brain of a keyboard-controlled robot. Every response it sends is within
+/// the configured limits, so it is held to the same rules as any other robot.
+struct HumanBrain {
+    keyboard: Keyboard,
+    config: Config,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Keyboard::default()
+    }
+
+    /// Update the held keys from a window event. The cursor's position in the
+    /// world is needed to aim the gun. Returns whether the event was used, in
+    /// which case it shouldn't also be handled by the viewer.
+    pub fn handle_event(&self, event: &Event, cursor: Vector2) -> bool {
+        let mut keys = self.keys.lock().unwrap();
+
+        match *event {
+            Event::KeyboardInput(state, _, Some(key)) => {
+                let pressed = state == ElementState::Pressed;
+
+                let held = match key {
+                    VirtualKeyCode::W => &mut keys.forward,
+                    VirtualKeyCode::S => &mut keys.back,
+                    VirtualKeyCode::A => &mut keys.left,
+                    VirtualKeyCode::D => &mut keys.right,
+                    VirtualKeyCode::Left => &mut keys.gun_left,
+                    VirtualKeyCode::Right => &mut keys.gun_right,
+                    _ => return false,
+                };
+
+                *held = pressed;
+                true
+            }
+
+            Event::MouseInput(state, MouseButton::Left) => {
+                keys.shooting = state == ElementState::Pressed;
+                keys.clicked = keys.clicked || keys.shooting;
+                true
+            }
+
+            // The viewer still needs to know where the mouse is.
+            Event::MouseMoved(..) => {
+                keys.aim = Some(cursor);
+                false
+            }
+
+            _ => false,
+        }
+    }
+}
+
+/// Create a controller for a robot driven by the given keyboard.
+pub fn new_ctl(id: u64, initial_pos: Vector2, config: Config, keyboard: Keyboard) -> Ctl {
+    let brain = HumanBrain {
+        keyboard: keyboard,
+        config: config.clone(),
+    };
+
+    Ctl::with_brain(id, initial_pos, config, brain)
+}
+
+impl HumanBrain {
+    /// The responses for a step, from the keys currently held.
+    fn step(&self, state: &BotState) -> Vec<Response> {
+        let config = &self.config;
+        let mut keys = self.keyboard.keys.lock().unwrap();
+
+        let thrust = match (keys.forward, keys.back) {
+            (true, false) => config.thrust_limits.max,
+            (false, true) => config.thrust_limits.min,
+            _ => 0.0,
+        };
+
+        // Headings go anticlockwise, so turning left is positive.
+        let turn_rate = match (keys.left, keys.right) {
+            (true, false) => config.turn_rate_limits.max,
+            (false, true) => config.turn_rate_limits.min,
+            _ => 0.0,
+        };
+
+        let gun_turn_rate = match (keys.gun_left, keys.gun_right, keys.aim) {
+            (true, false, _) => config.gun_turn_rate_limits.max,
+            (false, true, _) => config.gun_turn_rate_limits.min,
+
+            // Turn the gun to face the mouse a step after the next, when the
+            // rate set now takes effect. The gun also turns with the body,
+            // which has to be made up for.
+            (false, false, Some(aim)) => {
+                let next = predict(config, state, config.ticks_per_step);
+                let offset = angle_between(next.gun_heading, bearing(next.pos, aim));
+
+                config.gun_turn_rate_limits.clamp(offset / config.step_seconds() - turn_rate)
+            }
+
+            _ => 0.0,
+        };
+
+        let mut resps = vec![Response::SetThrust(thrust),
+                             Response::SetTurnRate(turn_rate),
+                             Response::SetGunTurnRate(gun_turn_rate),
+                             // Keep the radar sweeping so that scans show up.
+                             Response::SetRadarTurnRate(config.radar_turn_rate_limits.max)];
+
+        if keys.shooting || keys.clicked {
+            resps.push(Response::Shoot(config.bullet_power_limits.max));
+            keys.clicked = false;
+        }
+
+        resps
+    }
+}
+
+impl Brain for HumanBrain {
+    fn respond(&mut self, state: BotState, msg: Message) -> Result<Vec<Response>, String> {
+        let resps = match msg {
+            Message::Init { config } => {
+                self.config = config;
+                Vec::new()
+            }
+            Message::Step { .. } => self.step(&state),
+            _ => Vec::new(),
+        };
+
+        Ok(resps)
+    }
+}
@@ -1,7 +1,18 @@
+use math::Vector2;
+
 use std::time::Duration;
 use std::fmt::Debug;
 
+use rpc::{DebugDrawing, Response};
+use snapshot::{Bullet, Diagnostics};
+
 pub mod user;
+pub mod human;
+pub mod native;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "script")]
+pub mod script;
 
 /// A controller for a robot.
 pub trait RoboCtl {
@@ -24,4 +35,35 @@ pub trait RoboCtl {
 
     /// Fetch the public data.
     fn public_data(&self) -> &Self::PublicData;
+
+    /// Take the bullets fired since this was last called. The world fills in
+    /// each bullet's `owner`.
+    fn take_bullets(&mut self) -> Vec<Bullet>;
+
+    /// Take the debug messages printed since this was last called.
+    fn take_debug_prints(&mut self) -> Vec<String>;
+
+    /// The debug shapes the robot currently wants drawn.
+    fn debug_drawings(&self) -> &[DebugDrawing];
+
+    /// Take the responses the robot has sent since this was last called.
+    fn take_responses(&mut self) -> Vec<Response>;
+
+    /// Take a description of the error behind each strike the robot has been
+    /// given since this was last called.
+    fn take_strikes(&mut self) -> Vec<String>;
+
+    /// How well the controller is communicating with the robot.
+    fn diagnostics(&self) -> &Diagnostics;
+
+    /// Called when the robot is hit by a bullet doing the given damage.
+    fn hit(&mut self, damage: f64);
+
+    /// Called when the robot's radar sweeps over another robot at the given
+    /// position.
+    fn scan(&mut self, scan_pos: Vector2);
+
+    /// Called between ticks when the user asks for robots to be reloaded from
+    /// their source. Most robots can't be, and ignore this.
+    fn reload(&mut self) {}
 }
@@ -0,0 +1,50 @@
+//! Robots written against `RoboController` and hosted directly in the
+//! simulator, rather than in an external process. They see exactly what a
+//! process would, but nothing is serialised, which makes them cheap enough
+//! for built-in sparring partners.
+
+use math::*;
+use config::Config;
+use rpc::*;
+use ctl::user::{Brain, Ctl};
+
+use battlebots_support::bots;
+use battlebots_support::robo_controller::{Host, RoboController};
+
+pub use battlebots_support::bots::{NAMES, OPPONENTS};
+
+/// The prefix of the program name given in a match's header for robots that
+/// come with the crate, e.g. `builtin:spinner`.
+pub const PREFIX: &'static str = "builtin:";
+
+#[derive(Debug)]
+pub enum Error {
+    /// The program is of the form `builtin:NAME`, but there is no built-in
+    /// robot with this name.
+    UnknownBot(String),
+}
+
+impl<C: RoboController + Send> Brain for Host<C> {
+    fn respond(&mut self, state: BotState, msg: Message) -> Result<Vec<Response>, String> {
+        Ok(Host::respond(self, state, msg))
+    }
+}
+
+/// Create a controller for a robot run by the given `RoboController`.
+pub fn new_ctl<C>(id: u64, initial_pos: Vector2, config: Config, controller: C) -> Ctl
+    where C: RoboController + Send + 'static
+{
+    Ctl::with_brain(id, initial_pos, config, Host::new(controller))
+}
+
+/// The controller for a program name of the form `builtin:NAME`, or `None` if
+/// the program isn't built in.
+pub fn builtin(program: &str) -> Result<Option<Box<RoboController + Send>>, Error> {
+    if !program.starts_with(PREFIX) {
+        return Ok(None);
+    }
+
+    let name = &program[PREFIX.len()..];
+
+    bots::by_name(name).map(Some).ok_or_else(|| Error::UnknownBot(name.to_owned()))
+}
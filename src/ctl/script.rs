@@ -0,0 +1,323 @@
+//! Robots written as Rhai scripts, loaded directly by the simulator.
+//!
+//! A script may define any of these functions, mirroring `RoboController`:
+//!
+//! - `init()`
+//! - `step(elapsed)`
+//! - `scan(x, y)`
+//! - `kill()`
+//!
+//! They can call the same functions as `RoboHook`: getters for the robot's
+//! state (`pos_x()`, `pos_y()`, `heading()`, `gun_heading()`,
+//! `radar_heading()`, `rel_gun_heading()`, `rel_radar_heading()`, `speed()`,
+//! `hit_points()` and `shoot_power()`), `set_thrust`, `set_turn_rate`,
+//! `set_gun_turn_rate`, `set_radar_turn_rate`, `shoot`, `set_color` and
+//! `debug_print`. Setters clamp to the configured limits, like `RoboHook`.
+//!
+//! Script functions can't see global variables, so anything a robot wants to
+//! remember between calls goes in the properties of `this`, e.g. `this.ticks`.
+//!
+//! Each call is only allowed so many operations, and a script that goes over
+//! gets a strike. Scripts are reloaded from disk between ticks when the viewer
+//! asks, keeping what they remember in `this`, so they can be tweaked while a
+//! match runs. This is turned off in deterministic mode.
+
+use math::*;
+use config::Config;
+use rpc::*;
+use ctl::user::{Brain, Ctl};
+
+use rhai::{self, AST, CallFnOptions, Dynamic, Engine, FuncArgs, Scope};
+use rhai::packages::{Package, StandardPackage};
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The file extension of script robots, by which they are picked out from
+/// other programs in a match.
+pub const EXTENSION: &'static str = ".rhai";
+
+/// The most operations a script can run in a single call.
+const MAX_OPERATIONS: u64 = 100000;
+
+/// The largest strings, arrays and maps a script can make.
+const MAX_STRING_SIZE: usize = 4096;
+const MAX_COLLECTION_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(String),
+}
+
+/// What the script's functions can see and change during a call.
+struct HostState {
+    config: Config,
+    state: BotState,
+    resps: Vec<Response>,
+}
+
+/// A script, and what it remembers between calls.
+pub struct ScriptBrain {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    this: Dynamic,
+    host: Arc<Mutex<HostState>>,
+}
+
+impl ScriptBrain {
+    /// Load and compile a script from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let host = Arc::new(Mutex::new(HostState {
+            config: Config::default(),
+            state: BotState::default(),
+            resps: Vec::new(),
+        }));
+        let engine = new_engine(&host);
+        let ast = try!(compile(&engine, &path));
+
+        Ok(ScriptBrain {
+            path: path,
+            engine: engine,
+            ast: ast,
+            this: Dynamic::from_map(rhai::Map::new()),
+            host: host,
+        })
+    }
+
+    /// Call a function of the script's, if it has one.
+    fn call<A: FuncArgs>(&mut self, name: &str, args: A) -> Result<(), String> {
+        if !self.ast.iter_functions().any(|func| func.name == name) {
+            return Ok(());
+        }
+
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.this);
+
+        self.engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, name, args)
+            .map(|_| ())
+            .map_err(|err| format!("{}: {}", name, err))
+    }
+}
+
+impl Brain for ScriptBrain {
+    fn respond(&mut self, state: BotState, msg: Message) -> Result<Vec<Response>, String> {
+        {
+            let mut host = self.host.lock().unwrap();
+            host.state = state;
+
+            if let Message::Init { ref config } = msg {
+                host.config = config.clone();
+            }
+        }
+
+        let result = match msg {
+            Message::Init { .. } => self.call("init", ()),
+            Message::Step { elapsed } => self.call("step", (elapsed,)),
+            Message::Scan { scan_pos } => self.call("scan", (scan_pos.x, scan_pos.y)),
+            Message::Kill => self.call("kill", ()),
+        };
+
+        // This includes anything a reload printed since the last call.
+        let resps = self.host.lock().unwrap().resps.drain(..).collect();
+        result.map(|_| resps)
+    }
+
+    /// Recompile the script. A script that doesn't compile is reported, and
+    /// the old one kept.
+    fn reload(&mut self) {
+        let msg = match compile(&self.engine, &self.path) {
+            Ok(ast) => {
+                self.ast = ast;
+                format!("Reloaded {}", self.path.display())
+            }
+            Err(err) => format!("Couldn't reload {}: {:?}", self.path.display(), err),
+        };
+
+        self.host.lock().unwrap().resps.push(Response::DebugPrint(msg));
+    }
+}
+
+/// Create a controller for a robot loaded from a script.
+pub fn new_ctl<P: AsRef<Path>>(id: u64,
+                               initial_pos: Vector2,
+                               config: Config,
+                               path: P)
+                               -> Result<Ctl, Error> {
+    let brain = try!(ScriptBrain::load(path));
+    Ok(Ctl::with_brain(id, initial_pos, config, brain))
+}
+
+/// An engine with the robot functions registered, and limits on what scripts
+/// can do. Scripts have no access to files or anything else on the host.
+fn new_engine(host: &Arc<Mutex<HostState>>) -> Engine {
+    let mut engine = Engine::new_raw();
+    engine.register_global_module(StandardPackage::new().as_shared_module());
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_COLLECTION_SIZE);
+    engine.set_max_map_size(MAX_COLLECTION_SIZE);
+
+    macro_rules! getter {
+        ($name:expr, $field:expr) => {{
+            let host = host.clone();
+            engine.register_fn($name, move || -> f64 {
+                let host = host.lock().unwrap();
+                $field(&host.state)
+            });
+        }}
+    }
+
+    // Numbers can be given as integers or floats.
+    macro_rules! setter {
+        ($name:expr, $limits:ident, $resp:expr) => {{
+            let float_host = host.clone();
+            engine.register_fn($name, move |x: f64| {
+                let mut host = float_host.lock().unwrap();
+                let x = host.config.$limits.clamp(x);
+                host.resps.push($resp(x));
+            });
+
+            let int_host = host.clone();
+            engine.register_fn($name, move |x: i64| {
+                let mut host = int_host.lock().unwrap();
+                let x = host.config.$limits.clamp(x as f64);
+                host.resps.push($resp(x));
+            });
+        }}
+    }
+
+    getter!("pos_x", |state: &BotState| state.pos.x);
+    getter!("pos_y", |state: &BotState| state.pos.y);
+    getter!("heading", |state: &BotState| state.heading);
+    getter!("gun_heading", |state: &BotState| state.gun_heading);
+    getter!("radar_heading", |state: &BotState| state.radar_heading);
+    getter!("rel_gun_heading", |state: &BotState| state.gun_heading - state.heading);
+    getter!("rel_radar_heading", |state: &BotState| state.radar_heading - state.heading);
+    getter!("speed", |state: &BotState| state.speed);
+    getter!("hit_points", |state: &BotState| state.hit_points);
+    getter!("shoot_power", |state: &BotState| state.shoot_power);
+
+    setter!("set_thrust", thrust_limits, Response::SetThrust);
+    setter!("set_turn_rate", turn_rate_limits, Response::SetTurnRate);
+    setter!("set_gun_turn_rate", gun_turn_rate_limits, Response::SetGunTurnRate);
+    setter!("set_radar_turn_rate", radar_turn_rate_limits, Response::SetRadarTurnRate);
+    setter!("shoot", bullet_power_limits, Response::Shoot);
+
+    {
+        let host = host.clone();
+        engine.register_fn("set_color", move |red: f64, green: f64, blue: f64| {
+            let color = [red, green, blue];
+            let color = [color[0].max(0.0).min(1.0) as f32,
+                         color[1].max(0.0).min(1.0) as f32,
+                         color[2].max(0.0).min(1.0) as f32];
+            host.lock().unwrap().resps.push(Response::SetColor(color));
+        });
+    }
+
+    {
+        let host = host.clone();
+        engine.register_fn("debug_print", move |msg: &str| {
+            host.lock().unwrap().resps.push(Response::DebugPrint(msg.to_owned()));
+        });
+    }
+
+    engine
+}
+
+fn compile(engine: &Engine, path: &Path) -> Result<AST, Error> {
+    let mut source = String::new();
+    let mut file = try!(File::open(path).map_err(Error::Io));
+    try!(file.read_to_string(&mut source).map_err(Error::Io));
+
+    engine.compile(&source).map_err(|err| Error::Parse(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ctl::user::Brain;
+
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    /// Write a script to a file of its own in the temporary directory.
+    fn write_script(name: &str, source: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("battlebots-test-{}.rhai", name));
+        rewrite(&path, source);
+        path
+    }
+
+    fn rewrite(path: &Path, source: &str) {
+        File::create(path).unwrap().write_all(source.as_bytes()).unwrap();
+    }
+
+    fn step(brain: &mut ScriptBrain) -> Result<Vec<Response>, String> {
+        brain.respond(BotState::default(), Message::Step { elapsed: 0.5 })
+    }
+
+    fn print(msg: &str) -> Response {
+        Response::DebugPrint(msg.to_owned())
+    }
+
+    #[test]
+    fn functions_are_called_with_this() {
+        let path = write_script("this",
+                                "fn init() { this.greeting = \"hi\"; }
+                                 fn step(elapsed) { debug_print(this.greeting); }");
+        let mut brain = ScriptBrain::load(&path).unwrap();
+
+        brain.respond(BotState::default(), Message::Init { config: Config::default() }).unwrap();
+        assert_eq!(step(&mut brain).unwrap(), vec![print("hi")]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn going_over_the_operation_budget_is_an_error() {
+        let path = write_script("budget", "fn step(elapsed) { loop {} }");
+        let mut brain = ScriptBrain::load(&path).unwrap();
+
+        let err = step(&mut brain).unwrap_err();
+        assert!(err.contains("Too many operations"), "{}", err);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reloading_keeps_what_the_script_remembers() {
+        let path = write_script("reload",
+                                "fn step(elapsed) { this.steps = 1; debug_print(\"old\"); }");
+        let mut brain = ScriptBrain::load(&path).unwrap();
+        assert_eq!(step(&mut brain).unwrap(), vec![print("old")]);
+
+        rewrite(&path,
+                "fn step(elapsed) { this.steps += 1; debug_print(this.steps.to_string()); }");
+        brain.reload();
+
+        let reloaded = format!("Reloaded {}", path.display());
+        assert_eq!(step(&mut brain).unwrap(), vec![print(&reloaded), print("2")]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scripts_that_fail_to_reload_are_kept() {
+        let path = write_script("broken", "fn step(elapsed) { debug_print(\"old\"); }");
+        let mut brain = ScriptBrain::load(&path).unwrap();
+
+        rewrite(&path, "fn step(elapsed) {");
+        brain.reload();
+
+        let resps = step(&mut brain).unwrap();
+        assert_eq!(resps.len(), 2);
+        assert_eq!(resps[1], print("old"));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+use rpc::*;
+use super::Link;
+
+use std::collections::VecDeque;
+use std::mem;
+use std::time::{Duration, Instant};
+
+/// Makes a robot's decisions inside the simulator, answering each message with
+/// the same responses an external process would send. A brain that fails to
+/// answer a message, say by running out of its budget, gets a strike.
+pub trait Brain: Send {
+    fn respond(&mut self, state: BotState, msg: Message) -> Result<Vec<Response>, String>;
+
+    /// Reload the brain from its source, if it has one, keeping what it
+    /// remembers.
+    fn reload(&mut self) {}
+}
+
+/// A link to a brain in the simulator. Messages are answered straight away,
+/// on the robot's own thread, without any serialisation.
+pub struct Local<B> {
+    brain: B,
+    resp_queue: VecDeque<Vec<Response>>,
+    errors: Vec<String>,
+    latency: Duration,
+}
+
+impl<B: Brain> Local<B> {
+    pub fn new(brain: B) -> Self {
+        Local {
+            brain: brain,
+            resp_queue: VecDeque::new(),
+            errors: Vec::new(),
+            latency: Duration::new(0, 0),
+        }
+    }
+}
+
+impl<B: Brain> Link for Local<B> {
+    fn send_msg(&mut self, (state, msg): (BotState, Message)) {
+        let is_kill = match msg {
+            Message::Kill => true,
+            _ => false,
+        };
+
+        let start = Instant::now();
+        let resps = match self.brain.respond(state, msg) {
+            Ok(resps) => resps,
+            Err(err) => {
+                self.errors.push(err);
+                Vec::new()
+            }
+        };
+        self.latency = start.elapsed();
+
+        // Nothing waits for the responses to `Kill`.
+        if !is_kill {
+            self.resp_queue.push_back(resps);
+        }
+    }
+
+    fn try_recv_resps(&mut self) -> Option<Vec<Response>> {
+        self.resp_queue.pop_front()
+    }
+
+    fn recv_resps(&mut self) -> Option<Vec<Response>> {
+        self.resp_queue.pop_front()
+    }
+
+    fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    fn take_errors(&mut self) -> Vec<String> {
+        mem::replace(&mut self.errors, Vec::new())
+    }
+
+    fn reload(&mut self) {
+        self.brain.reload();
+    }
+}
@@ -2,35 +2,89 @@ use math::*;
 use config::*;
 use ctl::*;
 use rpc::*;
+use snapshot::{Bullet, Diagnostics};
+use physics::{self, duration_float};
+use responses::{self, DebugDrawings};
 
-use std::time::Duration;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use std::io::{BufReader, Read, Write};
 use std::thread;
 use std::fmt;
+use std::mem;
 
 mod process;
+mod local;
 
 use self::process::*;
+pub use self::local::Brain;
+use self::local::Local;
+
+/// The connection between a controller and whatever is making its robot's
+/// decisions. Every message sent, except `Kill`, is answered with a list of
+/// responses, in order.
+pub trait Link: Send {
+    fn send_msg(&mut self, msg: (BotState, Message));
+
+    /// Take the responses to the oldest unanswered message, if they have
+    /// arrived.
+    fn try_recv_resps(&mut self) -> Option<Vec<Response>>;
+
+    /// Wait for the responses to the oldest unanswered message, or return
+    /// `None` if the robot has stopped and will never answer it.
+    fn recv_resps(&mut self) -> Option<Vec<Response>>;
+
+    /// The time the robot took to answer its last message.
+    fn latency(&self) -> Duration;
+
+    /// Take the errors the robot has made since this was last called, other
+    /// than invalid responses.
+    fn take_errors(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Reload the robot from its source, if it can be.
+    fn reload(&mut self) {}
+}
 
-/// Controller for a user's robot, based on an external process.
+/// Controller for a user's robot, based on an external process or on a brain
+/// running in the simulator.
 pub struct Ctl {
     id: u64,
     ticks_until_step: u32,
     elapsed_since_step: f64,
 
+    /// The messages sent to the process which it hasn't responded to yet, in
+    /// order.
+    awaiting: VecDeque<Sent>,
+
+    /// When the robot was last sent a step, or initialised, in real time.
+    step_sent: Instant,
+
     next_shot_power: Option<f64>,
+    bullets: Vec<Bullet>,
+    debug_prints: Vec<String>,
+
+    debug_drawings: DebugDrawings,
+
+    /// The responses received since the world last took them.
+    responses: Vec<Response>,
+
+    /// The errors behind the strikes given since the world last took them.
+    strikes: Vec<String>,
+
+    diagnostics: Diagnostics,
 
     state: BotState,
     config: Config,
 
-    relay: Arc<Relay>,
+    link: Box<Link>,
 }
 
 impl fmt::Debug for Ctl {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f,
-               "Ctl {{ id: {}, ticks_until_steps: {}, state: {:?}, config: {:?}, relay: ?, \
+               "Ctl {{ id: {}, ticks_until_steps: {}, state: {:?}, config: {:?}, link: ?, \
                 join_handle: ? }}",
                self.id,
                self.ticks_until_step,
@@ -39,16 +93,29 @@ impl fmt::Debug for Ctl {
     }
 }
 
+/// A message the robot hasn't answered yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sent {
+    /// `Init` or `Step`. The robot isn't sent another step until it has
+    /// answered, and is slow if it takes too long.
+    Step,
+
+    /// `Scan`, which can be sent on any tick, and so is answered in the
+    /// robot's own time.
+    Scan,
+}
+
 #[derive(Debug)]
 pub enum Error {
     Process(ProcessError),
+    Brain(String),
     SlowResponse,
 
-    BadThrust(f64),
-    BadTurnRate(f64),
-    BadGunTurnRate(f64),
-    BadRadarTurnRate(f64),
-    TooManyBulletsPerFrame,
+    /// The robot stopped, say because its process exited, while it was being
+    /// waited for.
+    Disconnected,
+
+    BadResponse(responses::Error),
 }
 
 impl Ctl {
@@ -65,56 +132,108 @@ impl Ctl {
 
         thread::spawn(move || process.run());
 
+        Ctl::with_link(id, initial_pos, config, Box::new(relay))
+    }
+
+    /// Create a controller for a robot whose brain runs in the simulator,
+    /// rather than in an external process. The brain is held to the same
+    /// limits as a process.
+    pub fn with_brain<B>(id: u64, initial_pos: Vector2, config: Config, brain: B) -> Self
+        where B: Brain + 'static
+    {
+        Ctl::with_link(id, initial_pos, config, Box::new(Local::new(brain)))
+    }
+
+    fn with_link(id: u64, initial_pos: Vector2, config: Config, link: Box<Link>) -> Self {
         Ctl {
             id: id,
             ticks_until_step: config.ticks_per_step,
             elapsed_since_step: 0.0,
+            awaiting: VecDeque::new(),
+            step_sent: Instant::now(),
             next_shot_power: None,
-
-            state: BotState { pos: initial_pos, ..BotState::default() },
+            bullets: Vec::new(),
+            debug_prints: Vec::new(),
+            debug_drawings: DebugDrawings::new(),
+            responses: Vec::new(),
+            strikes: Vec::new(),
+            diagnostics: Diagnostics::default(),
+
+            state: BotState {
+                pos: initial_pos,
+                hit_points: config.max_hit_points,
+                shoot_power: config.max_shoot_power,
+                ..BotState::default()
+            },
             config: config,
 
-            relay: relay,
+            link: link,
         }
     }
 
-    /// Perform the effects of a single response.
-    fn apply_resp(&mut self, resp: Response) -> Result<(), Error> {
-        use rpc::Response::*;
-        use self::Error::*;
-
+    /// Perform the effects of a single response. `shot` is the power of the
+    /// shot asked for so far in the same list of responses.
+    fn apply_resp(&mut self, resp: Response, shot: &mut Option<f64>) -> Result<(), Error> {
         match resp {
-            SetThrust(x) => {
-                self.state.thrust = try!(self.config.thrust_limits.check(x).map_err(BadThrust))
+            Response::DebugPrint(msg) => {
+                println!("Bot {}: {}", self.id, msg);
+                self.debug_prints.push(msg);
+                Ok(())
             }
 
-            SetTurnRate(x) => {
-                self.state.turn_rate =
-                    try!(self.config.turn_rate_limits.check(x).map_err(BadTurnRate))
-            }
+            resp => {
+                let result = responses::apply(&self.config,
+                                              &mut self.state,
+                                              &mut self.debug_drawings,
+                                              shot,
+                                              resp);
+                self.diagnostics.dropped_drawings = self.debug_drawings.dropped();
 
-            SetGunTurnRate(x) => {
-                self.state.gun_turn_rate =
-                    try!(self.config.gun_turn_rate_limits.check(x).map_err(BadGunTurnRate))
+                result.map_err(Error::BadResponse)
             }
+        }
+    }
 
-            SetRadarTurnRate(x) => {
-                self.state.radar_turn_rate =
-                    try!(self.config.radar_turn_rate_limits.check(x).map_err(BadRadarTurnRate))
-            }
+    /// Whether the robot still has hit points left. Disqualified robots have
+    /// none.
+    fn is_alive(&self) -> bool {
+        self.state.hit_points > 0.0
+    }
 
-            Shoot(power) => {
-                if self.next_shot_power.is_none() {
-                    self.next_shot_power = Some(power);
-                } else {
-                    return Err(TooManyBulletsPerFrame)
-                }
-            }
+    /// Send a message to the process, which it will respond to.
+    fn send(&mut self, msg: Message) {
+        let sent = match msg {
+            Message::Scan { .. } => Sent::Scan,
+            _ => Sent::Step,
+        };
 
-            DebugPrint(msg) => println!("Bot {}: {}", self.id, msg),
+        self.awaiting.push_back(sent);
+        self.link.send_msg((self.state.clone(), msg));
+    }
+
+    /// Count an error in the robot's diagnostics, which the world records and
+    /// viewers show.
+    fn record_error(&mut self, err: &Error) {
+        self.diagnostics.errors += 1;
+        self.diagnostics.last_error = Some(format!("{:?}", err));
+    }
+
+    /// Give the robot a strike, disqualifying it if it has too many.
+    fn strike(&mut self, err: &Error) {
+        self.diagnostics.strikes += 1;
+        self.strikes.push(format!("{:?}", err));
+
+        if self.diagnostics.strikes >= self.config.max_strikes {
+            self.disqualify();
         }
+    }
 
-        Ok(())
+    /// Remove the robot from the match.
+    fn disqualify(&mut self) {
+        self.diagnostics.disqualified = true;
+        self.state.hit_points = 0.0;
+        self.debug_drawings.clear();
+        self.link.send_msg((self.state.clone(), Message::Kill));
     }
 }
 
@@ -123,12 +242,19 @@ impl RoboCtl for Ctl {
     type Error = Error;
 
     fn init(&mut self) -> Result<(), Error> {
-        self.relay.send_msg((self.state.clone(), Message::Init { config: self.config.clone() }));
+        let config = self.config.clone();
+        self.send(Message::Init { config: config });
+        self.step_sent = Instant::now();
 
         Ok(())
     }
 
     fn tick(&mut self, elapsed: Duration) -> Result<(), Error> {
+        // Dead robots stay where they are and no longer talk to their process.
+        if !self.is_alive() {
+            return Ok(());
+        }
+
         let elapsed = duration_float(elapsed);
 
         // Deal with external stepping. We use an asynchronous `Process` for
@@ -140,35 +266,124 @@ impl RoboCtl for Ctl {
         if self.ticks_until_step == 0 {
             self.ticks_until_step = self.config.ticks_per_step;
 
-            if let Some(resps) = self.relay.try_recv_resps() {
+            // Collect the responses to the messages sent since the last step
+            // which have been answered, in order. Each message's responses are
+            // applied on their own.
+            let mut answers = Vec::new();
+            let mut disconnected = false;
+
+            while !self.awaiting.is_empty() {
+                let next = if self.config.deterministic {
+                    let next = self.link.recv_resps();
+                    disconnected = next.is_none();
+                    next
+                } else {
+                    self.link.try_recv_resps()
+                };
+
+                match next {
+                    Some(next) => answers.push(next),
+                    None => break,
+                }
+
+                self.awaiting.pop_front();
+            }
+
+            // A robot that can't answer any more would hold up a deterministic
+            // match forever, so it is removed from it.
+            if disconnected {
+                self.record_error(&Error::Disconnected);
+                self.disqualify();
+                return Ok(());
+            }
+
+            // Only the last step decides whether the robot has caught up. Scans
+            // sent since can still be answered later without holding it back.
+            // Shapes without a duration only last until the robot's next step,
+            // which it has just responded to if it has caught up.
+            let caught_up = !self.awaiting.contains(&Sent::Step);
+            if caught_up {
+                self.debug_drawings.end_step();
+            }
+
+            // Every invalid response is an error, but the whole step only gets
+            // a single strike.
+            let mut first_error = None;
+
+            for err in self.link.take_errors() {
+                let err = Error::Brain(err);
+                self.record_error(&err);
+                if first_error.is_none() {
+                    first_error = Some(err);
+                }
+            }
+
+            for resps in answers {
+                let mut shot = None;
+
                 for resp in resps {
-                    try!(self.apply_resp(resp))
+                    self.responses.push(resp.clone());
+
+                    if let Err(err) = self.apply_resp(resp, &mut shot) {
+                        self.record_error(&err);
+                        if first_error.is_none() {
+                            first_error = Some(err);
+                        }
+                    }
                 }
 
-                self.relay.send_msg((self.state.clone(),
-                                     Message::Step { elapsed: self.elapsed_since_step }))
+                // A shot asked for in answer to a later message replaces one
+                // from an earlier message, as with every other setting.
+                if shot.is_some() {
+                    self.next_shot_power = shot;
+                }
+            }
+
+            self.diagnostics.latency = duration_float(self.link.latency());
+
+            if let Some(err) = first_error {
+                self.strike(&err);
+            }
+
+            if !self.is_alive() {
+                return Ok(());
+            }
+
+            if caught_up {
+                let elapsed_since_step = self.elapsed_since_step;
+                self.send(Message::Step { elapsed: elapsed_since_step });
+                self.elapsed_since_step = 0.0;
+                self.step_sent = Instant::now();
+            } else if self.step_sent.elapsed() < self.config.step_duration() {
+                // The world is running faster than real time, so the robot
+                // has had less than a step's worth of time to answer. It isn't
+                // sent another step yet, but isn't slow either.
             } else {
-                // TODO: Consider making this optionally not throw an error, or
-                // maybe only throw after blocking for longer.
-                return Err(Error::SlowResponse);
+                // The robot is still working on its last step, so it isn't sent
+                // another until it catches up. Being slow costs it steps, but
+                // isn't a strike.
+                self.record_error(&Error::SlowResponse);
             }
         }
 
         // Do the simulation
-        self.state.heading += self.state.turn_rate * elapsed;
-        self.state.gun_heading += (self.state.gun_turn_rate + self.state.turn_rate) * elapsed;
-        self.state.radar_heading += (self.state.radar_turn_rate + self.state.turn_rate) * elapsed;
-        self.state.speed += self.state.thrust * elapsed;
-        self.state.speed *= self.config.drive_friction;
+        physics::advance(&self.config, &mut self.state, elapsed);
 
-        let dir = Vector2::new(self.state.heading.cos(), self.state.heading.sin());
-        self.state.pos += dir * self.state.speed * elapsed;
+        self.debug_drawings.advance(elapsed);
+
+        let power = self.next_shot_power.take();
+        if let Some(bullet) = physics::shoot(&self.config, &mut self.state, power) {
+            self.bullets.push(bullet);
+        }
 
         Ok(())
     }
 
     fn kill(&mut self) -> Result<(), Error> {
-        self.relay.send_msg((self.state.clone(), Message::Kill));
+        // Dead robots have already been sent their `Kill` message.
+        if self.is_alive() {
+            self.link.send_msg((self.state.clone(), Message::Kill));
+        }
 
         Ok(())
     }
@@ -176,8 +391,52 @@ impl RoboCtl for Ctl {
     fn public_data(&self) -> &BotState {
         &self.state
     }
-}
 
-fn duration_float(d: Duration) -> f64 {
-    d.as_secs() as f64 + (d.subsec_nanos() / 1000u32) as f64 / 1000000.0
+    fn take_bullets(&mut self) -> Vec<Bullet> {
+        mem::replace(&mut self.bullets, Vec::new())
+    }
+
+    fn take_debug_prints(&mut self) -> Vec<String> {
+        mem::replace(&mut self.debug_prints, Vec::new())
+    }
+
+    fn debug_drawings(&self) -> &[DebugDrawing] {
+        self.debug_drawings.drawings()
+    }
+
+    fn take_responses(&mut self) -> Vec<Response> {
+        mem::replace(&mut self.responses, Vec::new())
+    }
+
+    fn take_strikes(&mut self) -> Vec<String> {
+        mem::replace(&mut self.strikes, Vec::new())
+    }
+
+    fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    fn hit(&mut self, damage: f64) {
+        if !self.is_alive() {
+            return;
+        }
+
+        self.state.hit_points -= damage;
+
+        if !self.is_alive() {
+            self.state.hit_points = 0.0;
+            self.debug_drawings.clear();
+            self.link.send_msg((self.state.clone(), Message::Kill));
+        }
+    }
+
+    fn scan(&mut self, scan_pos: Vector2) {
+        if self.is_alive() {
+            self.send(Message::Scan { scan_pos: scan_pos });
+        }
+    }
+
+    fn reload(&mut self) {
+        self.link.reload();
+    }
 }
@@ -1,6 +1,8 @@
 use rpc::*;
+use super::Link;
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::io;
 use std::io::{BufRead, Write};
 
@@ -27,7 +29,12 @@ pub enum ProcessError {
 /// A process's message and response relay.
 pub struct Relay {
     msg_queue: MsQueue<(BotState, Message)>,
-    resp_queue: MsQueue<Vec<Response>>,
+
+    /// Each list of responses, in order. `None` marks that the process has
+    /// stopped relaying, and is always left at the front once reached so that
+    /// everything waiting sees it.
+    resp_queue: MsQueue<Option<Vec<Response>>>,
+    latency: Mutex<Duration>,
 }
 
 impl<R, W> Process<R, W> {
@@ -47,19 +54,30 @@ impl<R, W> Process<R, W> {
 impl<R, W> Process<R, W>
     where R: BufRead,
           W: Write {
-    /// Start relaying messages.
+    /// Start relaying messages, until the process can no longer be talked to.
     pub fn run(mut self) -> Result<(), ProcessError> {
+        let result = self.relay_msgs();
+
+        // Wake anything waiting for responses which will now never come.
+        self.relay.resp_queue.push(None);
+
+        result
+    }
+
+    fn relay_msgs(&mut self) -> Result<(), ProcessError> {
         loop {
             // Relay a waiting message from the queue to the child process
             let msg = self.relay.recv_msg();
             let ser = try!(serde_json::to_string(&msg).map_err(ProcessError::Serialization));
             try!(writeln!(self.output_writer, "{}", ser).map_err(ProcessError::Writing));
+            let sent_time = Instant::now();
 
             // Receive a list of responses from the child process
             let mut next_line = String::new();
             try!(self.input_reader.read_line(&mut next_line).map_err(ProcessError::Reading));
             let resps = try!(serde_json::from_str(next_line.as_str())
                 .map_err(ProcessError::Deserialization));
+            *self.relay.latency.lock().unwrap() = sent_time.elapsed();
             self.relay.send_resps(resps);
         }
     }
@@ -70,6 +88,7 @@ impl Relay {
         Relay {
             msg_queue: MsQueue::new(),
             resp_queue: MsQueue::new(),
+            latency: Mutex::new(Duration::new(0, 0)),
         }
     }
 
@@ -80,11 +99,37 @@ impl Relay {
 
     /// Try to receive a single list of responses from the external process.
     pub fn try_recv_resps(&self) -> Option<Vec<Response>> {
-        self.resp_queue.try_pop()
+        match self.resp_queue.try_pop() {
+            Some(Some(resps)) => Some(resps),
+            Some(None) => {
+                self.resp_queue.push(None);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Wait until a single list of responses is received from the external
+    /// process. Returns `None` if the process has stopped, such as by exiting,
+    /// so none will ever come.
+    pub fn recv_resps(&self) -> Option<Vec<Response>> {
+        match self.resp_queue.pop() {
+            Some(resps) => Some(resps),
+            None => {
+                self.resp_queue.push(None);
+                None
+            }
+        }
+    }
+
+    /// The time between the last message being sent to the external process
+    /// and its responses coming back.
+    pub fn latency(&self) -> Duration {
+        *self.latency.lock().unwrap()
     }
 
     fn send_resps(&self, resp: Vec<Response>) {
-        self.resp_queue.push(resp);
+        self.resp_queue.push(Some(resp));
     }
 
     fn recv_msg(&self) -> (BotState, Message) {
@@ -92,6 +137,24 @@ impl Relay {
     }
 }
 
+impl Link for Arc<Relay> {
+    fn send_msg(&mut self, msg: (BotState, Message)) {
+        Relay::send_msg(self, msg)
+    }
+
+    fn try_recv_resps(&mut self) -> Option<Vec<Response>> {
+        Relay::try_recv_resps(self)
+    }
+
+    fn recv_resps(&mut self) -> Option<Vec<Response>> {
+        Relay::recv_resps(self)
+    }
+
+    fn latency(&self) -> Duration {
+        Relay::latency(self)
+    }
+}
+
 impl Default for Relay {
     fn default() -> Self {
         Relay::new()
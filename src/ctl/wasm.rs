@@ -0,0 +1,405 @@
+//! Robots compiled to WebAssembly, run in a sandbox inside the simulator.
+//!
+//! A robot module may export any of these functions, mirroring
+//! `RoboController`:
+//!
+//! - `init()`
+//! - `step(elapsed: f64)`
+//! - `scan(x: f64, y: f64)`
+//! - `kill()`
+//!
+//! None of them return anything, and a module exporting one of them with other
+//! parameters is rejected when it is loaded.
+//!
+//! It can import only the functions in the `bot` module: getters for its
+//! state (`pos_x`, `pos_y`, `heading`, `gun_heading`, `radar_heading`,
+//! `speed`, `hit_points` and `shoot_power`), `set_thrust`, `set_turn_rate`,
+//! `set_gun_turn_rate`, `set_radar_turn_rate`, `shoot` and `set_color`, which
+//! take the same values as the responses of the same names, and
+//! `debug_print(ptr: i32, len: i32)`, which prints UTF-8 text from its exported
+//! `memory`. Nothing else on the host is reachable.
+//!
+//! Each call is given a fixed amount of fuel, which running instructions uses
+//! up, and memory can only grow so far. A robot which runs out of either gets a
+//! strike, and its responses for that call are dropped.
+
+use math::*;
+use config::Config;
+use rpc::*;
+use ctl::user::{Brain, Ctl};
+
+use wasmi::{self, Caller, Engine, Instance, Linker, Module, Store, StoreLimits,
+            StoreLimitsBuilder};
+use wasmi::core::ValType;
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The file extension of WebAssembly robots, by which they are picked out from
+/// other programs in a match.
+pub const EXTENSION: &'static str = ".wasm";
+
+/// The longest text, in bytes, a robot can print at once.
+const MAX_PRINT_LEN: usize = 4096;
+
+/// The functions a robot may export, and the parameters of each.
+const EXPORTS: &'static [(&'static str, &'static [ValType])] = &[("init", &[]),
+                                                                 ("step", &[ValType::F64]),
+                                                                 ("scan",
+                                                                  &[ValType::F64, ValType::F64]),
+                                                                 ("kill", &[])];
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Wasm(String),
+    /// The module exports a robot function with the wrong signature.
+    BadExport(String),
+}
+
+/// How much a robot is allowed to do.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// The fuel given for each call into the robot, roughly one per
+    /// instruction.
+    pub fuel_per_call: u64,
+
+    /// The most memory the robot can have, in bytes.
+    pub max_memory: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            fuel_per_call: 1000000,
+            max_memory: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// What the robot's imports can see and change during a call.
+struct HostState {
+    state: BotState,
+    resps: Vec<Response>,
+    limits: StoreLimits,
+}
+
+/// A robot module, instantiated in its own store.
+pub struct WasmBrain {
+    store: Store<HostState>,
+    instance: Instance,
+    limits: Limits,
+}
+
+impl WasmBrain {
+    /// Load and instantiate a robot from a `.wasm` file.
+    pub fn load<P: AsRef<Path>>(path: P, limits: Limits) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        let mut file = try!(File::open(path).map_err(Error::Io));
+        try!(file.read_to_end(&mut bytes).map_err(Error::Io));
+
+        WasmBrain::new(&bytes, limits)
+    }
+
+    /// Instantiate a robot from the bytes of a module.
+    pub fn new(bytes: &[u8], limits: Limits) -> Result<Self, Error> {
+        let mut engine_config = wasmi::Config::default();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config);
+
+        let module = try!(Module::new(&engine, bytes).map_err(wasm_error));
+        try!(check_exports(&module));
+
+        let host_state = HostState {
+            state: BotState::default(),
+            resps: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(limits.max_memory)
+                .instances(1)
+                .build(),
+        };
+
+        let mut store = Store::new(&engine, host_state);
+        store.limiter(|host| &mut host.limits);
+
+        // Running the module's start function uses fuel too.
+        try!(store.set_fuel(limits.fuel_per_call).map_err(wasm_error));
+
+        let linker = try!(link(&engine));
+        let instance = try!(linker.instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(wasm_error));
+
+        Ok(WasmBrain {
+            store: store,
+            instance: instance,
+            limits: limits,
+        })
+    }
+
+    /// Call an export of the robot's, if it has one, with a fresh tank of fuel.
+    fn call<Params>(&mut self, name: &str, params: Params) -> Result<(), String>
+        where Params: wasmi::WasmParams
+    {
+        // The exports were checked on loading, so the lookup can only fail if
+        // the robot doesn't have this one.
+        let func = match self.instance.get_typed_func::<Params, ()>(&self.store, name) {
+            Ok(func) => func,
+            Err(_) => return Ok(()),
+        };
+
+        try!(self.store.set_fuel(self.limits.fuel_per_call).map_err(|err| err.to_string()));
+
+        func.call(&mut self.store, params).map_err(|err| format!("{}: {}", name, err))
+    }
+}
+
+impl Brain for WasmBrain {
+    fn respond(&mut self, state: BotState, msg: Message) -> Result<Vec<Response>, String> {
+        self.store.data_mut().state = state;
+        self.store.data_mut().resps.clear();
+
+        let result = match msg {
+            Message::Init { .. } => self.call("init", ()),
+            Message::Step { elapsed } => self.call("step", elapsed),
+            Message::Scan { scan_pos } => self.call("scan", (scan_pos.x, scan_pos.y)),
+            Message::Kill => self.call("kill", ()),
+        };
+
+        let resps = self.store.data_mut().resps.drain(..).collect();
+        result.map(|_| resps)
+    }
+}
+
+/// Create a controller for a robot loaded from a `.wasm` file.
+pub fn new_ctl<P: AsRef<Path>>(id: u64,
+                               initial_pos: Vector2,
+                               config: Config,
+                               path: P)
+                               -> Result<Ctl, Error> {
+    let brain = try!(WasmBrain::load(path, Limits::default()));
+    Ok(Ctl::with_brain(id, initial_pos, config, brain))
+}
+
+/// Check that each robot function the module exports takes the parameters it
+/// will be called with, and returns nothing.
+fn check_exports(module: &Module) -> Result<(), Error> {
+    for export in module.exports() {
+        let params = match EXPORTS.iter().find(|&&(name, _)| name == export.name()) {
+            Some(&(_, params)) => params,
+            None => continue,
+        };
+
+        let ok = match export.ty().func() {
+            Some(func) => func.params() == params && func.results().is_empty(),
+            None => false,
+        };
+
+        if !ok {
+            return Err(Error::BadExport(format!("`{}` should be a function taking {:?} and \
+                                                 returning nothing",
+                                                export.name(),
+                                                params)));
+        }
+    }
+
+    Ok(())
+}
+
+/// The imports robots can use, and nothing more.
+fn link(engine: &Engine) -> Result<Linker<HostState>, Error> {
+    let mut linker = Linker::new(engine);
+
+    macro_rules! getter {
+        ($name:expr, $field:expr) => {
+            try!(linker.func_wrap("bot", $name, |caller: Caller<HostState>| -> f64 {
+                    let state = &caller.data().state;
+                    $field(state)
+                })
+                .map_err(wasm_error));
+        }
+    }
+
+    macro_rules! respond {
+        ($name:expr, $resp:expr) => {
+            try!(linker.func_wrap("bot", $name, |mut caller: Caller<HostState>, x: f64| {
+                    caller.data_mut().resps.push($resp(x));
+                })
+                .map_err(wasm_error));
+        }
+    }
+
+    getter!("pos_x", |state: &BotState| state.pos.x);
+    getter!("pos_y", |state: &BotState| state.pos.y);
+    getter!("heading", |state: &BotState| state.heading);
+    getter!("gun_heading", |state: &BotState| state.gun_heading);
+    getter!("radar_heading", |state: &BotState| state.radar_heading);
+    getter!("speed", |state: &BotState| state.speed);
+    getter!("hit_points", |state: &BotState| state.hit_points);
+    getter!("shoot_power", |state: &BotState| state.shoot_power);
+
+    respond!("set_thrust", Response::SetThrust);
+    respond!("set_turn_rate", Response::SetTurnRate);
+    respond!("set_gun_turn_rate", Response::SetGunTurnRate);
+    respond!("set_radar_turn_rate", Response::SetRadarTurnRate);
+    respond!("shoot", Response::Shoot);
+
+    let set_color = |mut caller: Caller<HostState>, red: f32, green: f32, blue: f32| {
+        caller.data_mut().resps.push(Response::SetColor([red, green, blue]));
+    };
+    try!(linker.func_wrap("bot", "set_color", set_color).map_err(wasm_error));
+
+    // Text that is too long, isn't in memory, or isn't UTF-8, traps.
+    let debug_print = |mut caller: Caller<HostState>, ptr: i32, len: i32| {
+        if len as u32 as usize > MAX_PRINT_LEN {
+            return Err(wasmi::Error::new("debug_print text too long"));
+        }
+
+        let memory = try!(caller.get_export("memory")
+            .and_then(|export| export.into_memory())
+            .ok_or_else(|| wasmi::Error::new("debug_print needs an exported memory")));
+
+        let mut buf = vec![0; len as u32 as usize];
+        try!(memory.read(&caller, ptr as u32 as usize, &mut buf)
+            .map_err(|err| wasmi::Error::new(err.to_string())));
+
+        let msg = try!(String::from_utf8(buf).map_err(|err| wasmi::Error::new(err.to_string())));
+
+        caller.data_mut().resps.push(Response::DebugPrint(msg));
+        Ok(())
+    };
+    try!(linker.func_wrap("bot", "debug_print", debug_print).map_err(wasm_error));
+
+    Ok(linker)
+}
+
+fn wasm_error<E: Display>(err: E) -> Error {
+    Error::Wasm(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ctl::user::Brain;
+
+    /// The magic number and version every module starts with.
+    const HEADER: &'static [u8] = &[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    /// Exports `step(f32)`, which takes the wrong type.
+    const BAD_STEP: &'static [u8] = &[
+        // Types: (f32) -> ()
+        0x01, 0x05, 0x01, 0x60, 0x01, 0x7d, 0x00,
+        // Functions: one of type 0
+        0x03, 0x02, 0x01, 0x00,
+        // Exports: "step" is function 0
+        0x07, 0x08, 0x01, 0x04, b's', b't', b'e', b'p', 0x00, 0x00,
+        // Code: an empty body
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+
+    /// Exports `step(f64)`, which loops forever.
+    const ENDLESS_STEP: &'static [u8] = &[
+        // Types: (f64) -> ()
+        0x01, 0x05, 0x01, 0x60, 0x01, 0x7c, 0x00,
+        // Functions: one of type 0
+        0x03, 0x02, 0x01, 0x00,
+        // Exports: "step" is function 0
+        0x07, 0x08, 0x01, 0x04, b's', b't', b'e', b'p', 0x00, 0x00,
+        // Code: loop, br 0, end
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x0b,
+    ];
+
+    /// Starts with 257 pages of memory, one more than the default limit.
+    const BIG_MEMORY: &'static [u8] = &[
+        // Memory: at least 257 pages
+        0x05, 0x04, 0x01, 0x00, 0x81, 0x02,
+    ];
+
+    /// Exports `step(f64)`, which sets its thrust to the elapsed time and
+    /// prints "hi" from its memory.
+    const THRUST_AND_PRINT: &'static [u8] = &[
+        // Types: (f64) -> () and (i32, i32) -> ()
+        0x01, 0x0a, 0x02, 0x60, 0x01, 0x7c, 0x00, 0x60, 0x02, 0x7f, 0x7f, 0x00,
+        // Imports: bot.set_thrust of type 0 and bot.debug_print of type 1
+        0x02, 0x24, 0x02,
+        0x03, b'b', b'o', b't',
+        0x0a, b's', b'e', b't', b'_', b't', b'h', b'r', b'u', b's', b't', 0x00, 0x00,
+        0x03, b'b', b'o', b't',
+        0x0b, b'd', b'e', b'b', b'u', b'g', b'_', b'p', b'r', b'i', b'n', b't', 0x00, 0x01,
+        // Functions: one of type 0
+        0x03, 0x02, 0x01, 0x00,
+        // Memory: a single page
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        // Exports: "step" is function 2, and "memory" is memory 0
+        0x07, 0x11, 0x02,
+        0x04, b's', b't', b'e', b'p', 0x00, 0x02,
+        0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+        // Code: set_thrust(elapsed), debug_print(0, 2)
+        0x0a, 0x0e, 0x01, 0x0c, 0x00,
+        0x20, 0x00, 0x10, 0x00,
+        0x41, 0x00, 0x41, 0x02, 0x10, 0x01,
+        0x0b,
+        // Data: "hi" at address 0
+        0x0b, 0x08, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x02, b'h', b'i',
+    ];
+
+    fn module(sections: &[u8]) -> Vec<u8> {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(sections);
+        bytes
+    }
+
+    fn step(brain: &mut WasmBrain) -> Result<Vec<Response>, String> {
+        brain.respond(BotState::default(), Message::Step { elapsed: 0.5 })
+    }
+
+    #[test]
+    fn exports_are_called_with_imports() {
+        let mut brain = WasmBrain::new(&module(THRUST_AND_PRINT), Limits::default()).unwrap();
+
+        assert_eq!(step(&mut brain).unwrap(),
+                   vec![Response::SetThrust(0.5), Response::DebugPrint("hi".to_owned())]);
+    }
+
+    #[test]
+    fn missing_exports_are_skipped() {
+        let mut brain = WasmBrain::new(&module(&[]), Limits::default()).unwrap();
+        assert_eq!(step(&mut brain).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn badly_typed_exports_are_rejected() {
+        match WasmBrain::new(&module(BAD_STEP), Limits::default()) {
+            Err(Error::BadExport(_)) => {}
+            other => panic!("Expected a bad export, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn running_out_of_fuel_is_an_error() {
+        let limits = Limits { fuel_per_call: 10000, ..Limits::default() };
+        let mut brain = WasmBrain::new(&module(ENDLESS_STEP), limits).unwrap();
+
+        assert!(step(&mut brain).is_err());
+    }
+
+    #[test]
+    fn fuel_is_refilled_for_each_call() {
+        let limits = Limits { fuel_per_call: 20, ..Limits::default() };
+        let mut brain = WasmBrain::new(&module(THRUST_AND_PRINT), limits).unwrap();
+
+        for _ in 0..10 {
+            assert!(step(&mut brain).is_ok());
+        }
+    }
+
+    #[test]
+    fn memory_is_capped() {
+        assert!(WasmBrain::new(&module(BIG_MEMORY), Limits::default()).is_err());
+
+        let limits = Limits { max_memory: 1024 * 1024 * 1024, ..Limits::default() };
+        assert!(WasmBrain::new(&module(BIG_MEMORY), limits).is_ok());
+    }
+}
@@ -4,6 +4,13 @@ extern crate serde_json;
 #[macro_use]
 extern crate glium;
 extern crate crossbeam;
+extern crate png;
+extern crate gif;
+extern crate ws;
+#[cfg(feature = "wasm")]
+extern crate wasmi;
+#[cfg(feature = "script")]
+extern crate rhai;
 
 extern crate render_utils;
 extern crate battlebots_support;
@@ -12,11 +19,24 @@ pub mod ctl;
 pub mod robo;
 pub mod world;
 pub mod render;
+pub mod view;
+pub mod tui;
+pub mod raster;
+pub mod svg;
+pub mod spectate;
 pub mod threading;
+pub mod recorder;
+pub mod playback;
+pub mod verify;
 
 pub use battlebots_support::math;
 pub use battlebots_support::config;
 pub use battlebots_support::rpc;
+pub use battlebots_support::snapshot;
+pub use battlebots_support::replay;
+pub use battlebots_support::physics;
+pub use battlebots_support::responses;
+pub use battlebots_support::targeting;
 
 #[cfg(test)]
 mod tests {
@@ -1,52 +1,656 @@
 extern crate battlebots;
 extern crate rand;
-extern crate glium;
 
-use glium::Surface;
-use glium::glutin;
+use rand::{Rng, SeedableRng};
+use rand::isaac::Isaac64Rng;
 
-use battlebots::world::World;
+use battlebots::world::{self, World, TickLock};
 use battlebots::ctl::user::Ctl;
+use battlebots::ctl::human::{self, Keyboard};
+use battlebots::ctl::native;
+#[cfg(feature = "wasm")]
+use battlebots::ctl::wasm;
+#[cfg(feature = "script")]
+use battlebots::ctl::script;
 use battlebots::math::Vector2;
 use battlebots::config::Config;
-use battlebots::render::DrawState;
+use battlebots::render::GlViewer;
+use battlebots::tui::{self, TextViewer};
+use battlebots::raster::{self, Rasterizer};
+use battlebots::svg;
+use battlebots::spectate;
+use battlebots::view::{Input, Viewer};
 use battlebots::threading::Coordinator;
+use battlebots::recorder::Recorder;
+use battlebots::replay::{self, Header, BotInfo, Reader};
+use battlebots::playback::{Player, Control};
+use battlebots::snapshot::Snapshot;
+use battlebots::verify::{self, Difference};
 
+use std::collections::VecDeque;
 use std::env;
-use std::process::{Command, Stdio};
+use std::fs::File;
+use std::path::Path;
+use std::io::{BufReader, BufWriter};
+use std::process::{self, Command, Stdio};
 use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+/// The keys and mouse controls of the window viewer.
+const VIEW_KEYS: &'static str = "Space: pause, ./,: step, +/-: speed, ]/[: next/previous hit, \
+                                 Home/End: start/end, digits then Return: jump to tick, \
+                                 drag/scroll: pan/zoom, Tab/Escape: follow/unfollow robot, \
+                                 click: inspect robot, L: labels, R: radar sweeps, \
+                                 H: scoreboard, S: sort scoreboard, D: debug drawings, \
+                                 V: save SVG, F5: reload scripts";
+
+/// The controls of a keyboard-controlled robot, which take the place of any
+/// viewer keys they share.
+const HUMAN_KEYS: &'static str = "W/S: thrust, A/D: turn, Left/Right or mouse: aim, click: shoot";
+
+/// The default size of the text viewer's arena, in characters, and how many
+/// times a second it is redrawn.
+const TEXT_SIZE: (usize, usize) = (60, 30);
+const TEXT_RATE: u32 = 10;
+
+/// The default width of exported images, in pixels, and the frame rate of
+/// exported GIFs.
+const IMAGE_WIDTH: u32 = 800;
+const GIF_FRAMES_PER_SECOND: u32 = 20;
+
+/// The most snapshots that can wait to be drawn into a live GIF.
+const GIF_QUEUE_LEN: usize = 64;
+
+/// The most snapshots that can wait to be streamed to spectators.
+const SPECTATE_QUEUE_LEN: usize = 64;
+
+/// How many matches a benchmarked robot plays against each reference opponent
+/// by default, and how long each can last, in seconds.
+const BENCH_MATCHES: u64 = 10;
+const BENCH_SECONDS: u64 = 120;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("replay") => play_replay(&args[2..]),
+        Some("verify") => verify_replay(&args[2..]),
+        Some("bench") => run_benchmark(&args[2..]),
+        Some("png") => export_pngs(&args[2..]),
+        Some("gif") => export_gif(&args[2..]),
+        Some("svg") => export_svg(&args[2..]),
+        _ => run_match(),
+    }
+}
+
+/// Run a live match between robots from external processes, drawing it until
+/// the window is closed.
+fn run_match() {
     // TODO: load from disk or environment variables?
-    let config = Config::default();
+    let mut config = Config::default();
+    config.deterministic = env::var("DETERMINISTIC").is_ok();
 
     let num_bots = 100;
 
+    // All randomness in setting up the match comes from this seed, so that it
+    // can be recorded.
+    let seed = env::var("SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(rand::random);
+
+    // Robots take turns at each of these programs. Built-in robots are given
+    // as `builtin:NAME`, and they, WebAssembly and script robots run in the
+    // simulator.
+    let programs: Vec<String> = env::var("TESTPRG")
+        .unwrap()
+        .split(',')
+        .map(|program| program.to_string())
+        .collect();
+
+    // The first robot is driven from the keyboard, if asked for. Only the
+    // window can take its input.
+    let human = env::var("HUMAN").is_ok();
+
+    if human && text_viewer() {
+        println!("HUMAN needs the window viewer, so can't be used with VIEWER=text.");
+        process::exit(1);
+    }
+
+    // Robots are split evenly between this many teams, if given.
+    let num_teams = env::var("TEAMS").ok().map(|teams| {
+        let teams: u32 = teams.parse().expect("TEAMS must be a number");
+        assert!(teams > 0, "TEAMS must be at least 1");
+        teams
+    });
+
+    let header = Header {
+        version: replay::VERSION,
+        config: config.clone(),
+        seed: seed,
+        bots: (0..num_bots)
+            .map(|id| {
+                BotInfo {
+                    id: id,
+                    program: if human && id == 0 {
+                        human::PROGRAM.to_string()
+                    } else {
+                        programs[id as usize % programs.len()].clone()
+                    },
+                    team: num_teams.map(|teams| id as u32 % teams),
+                }
+            })
+            .collect(),
+    };
+
+    check_builtins(&header);
+
+    println!("Starting robot processes...");
+
+    let keyboard = Keyboard::new();
+    let ctls = spawn_ctls(&header, Some(&keyboard));
+
+    println!("Starting the simulation...");
+
+    let (mut world, tick_lock, stop_world) = World::new(config.clone(), ctls);
+
+    // Record the match if a replay file is given.
+    let recorder = env::var("RECORD").ok().map(|path| {
+        let file = BufWriter::new(File::create(path).unwrap());
+        Recorder::new(file, &header, world.observe()).unwrap()
+    });
+
+    // Spectators can fall behind the match, in which case they miss ticks.
+    let _spectate_server = spectate(&header, world.observe_lossy(SPECTATE_QUEUE_LEN));
+
+    // Draw the match into an animated GIF if a file is given. Drawing can fall
+    // behind the match, in which case frames are dropped.
+    let gif_writer = env::var("GIF").ok().map(|path| {
+        let rasterizer = Rasterizer::new(&config, &header.bots, image_width());
+        let ticks_per_frame = gif_ticks_per_frame(&config);
+        let snapshots = world.observe_lossy(GIF_QUEUE_LEN);
+
+        thread::spawn(move || {
+            let file = BufWriter::new(File::create(path).unwrap());
+            raster::write_gif(file, &rasterizer, snapshots, ticks_per_frame)
+        })
+    });
+
+    // Every tick's snapshot goes to the drawing thread.
+    let snapshots = world.observe();
+
+    let controls = world.controller();
+
+    // the coordinator for drawing, world running, and robots
+    let mut main_coord = Coordinator::new();
+
+    spawn_world(world, tick_lock, &mut main_coord);
+
+    // Do drawing in its own coordinated thread
+    {
+        let config = config.clone();
+        let bots = header.bots.clone();
+        let keyboard = if human { Some(keyboard) } else { None };
+
+        main_coord.spawn(AssertUnwindSafe(move || {
+            // Live matches can only be paused, stepped, sped up and have their
+            // scripts reloaded.
+            view(&config, &bots, snapshots, keyboard, &mut |input| {
+                let control = match input {
+                    Input::TogglePause => Some(world::Control::TogglePause),
+                    Input::StepForward => Some(world::Control::Step),
+                    Input::Faster => Some(world::Control::Faster),
+                    Input::Slower => Some(world::Control::Slower),
+                    Input::Reload => Some(world::Control::Reload),
+                    _ => None,
+                };
+
+                if let Some(control) = control {
+                    let _ = controls.send(control);
+                }
+            })
+        }));
+    }
+
+    // Wait for the first of the drawing, world or robots thread to end. If
+    // there are no errors, this will always be the drawing thread (when the
+    // user closes the window).
+    main_coord.next().unwrap().expect("Drawing, world or robots thread panicked");
+
+    // If the world hasn't already stopped (which it shouldn't have unless there
+    // was a panic), tell it to stop. It will in turn tell each of the robots to
+    // stop.
+    let _ = stop_world.send(());
+
+    // Wait for other running threads to finish up.
+    for res in main_coord {
+        res.expect("Panic at shutdown.");
+    }
+
+    // Finish writing the replay and GIF, now that the world can't send any
+    // more to them.
+    drop(recorder);
+
+    if let Some(gif_writer) = gif_writer {
+        match gif_writer.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => println!("Error while writing GIF: {:?}", err),
+            Err(_) => println!("GIF writing thread panicked"),
+        }
+    }
+
+    println!("Goodbye!");
+}
+
+/// Re-run a match recorded in deterministic mode from its replay header, and
+/// check every tick against the recording. Exits with an error at the first
+/// difference, including the re-run ending before the recording does.
+fn verify_replay(args: &[String]) {
+    let path = args.get(0).expect("Usage: battlebots verify FILE");
+
+    let reader = Reader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    let header = reader.header().clone();
+
+    if !header.config.deterministic {
+        println!("The replay was not recorded in deterministic mode, so can't be verified.");
+        process::exit(1);
+    }
+
+    // What a person did at the keyboard can't be played again.
+    if header.bots.iter().any(|bot| bot.program == human::PROGRAM) {
+        println!("The replay has a keyboard-controlled robot, so can't be verified.");
+        process::exit(1);
+    }
+
+    check_builtins(&header);
+
     println!("Starting robot processes...");
 
-    // Create some robots from external processes.
-    let ctls: Vec<Ctl> = (0..num_bots)
-        .map(|id| {
-            let child = Command::new(env::var("TESTPRG").unwrap())
+    let ctls = spawn_ctls(&header, None);
+
+    println!("Verifying...");
+
+    let (mut world, tick_lock, stop_world) = World::new(header.config.clone(), ctls);
+    world.set_realtime(false);
+
+    let snapshots = world.observe();
+
+    let mut main_coord = Coordinator::new();
+
+    spawn_world(world, tick_lock, &mut main_coord);
+
+    let mut ticks = 0;
+    let mut divergence = None;
+
+    for recorded in reader {
+        let recorded = recorded.unwrap();
+        let actual = match snapshots.recv() {
+            Ok(actual) => actual,
+            Err(_) => {
+                divergence = Some(Difference {
+                    tick: recorded.tick,
+                    field: "tick".to_owned(),
+                    expected: recorded.tick.to_string(),
+                    actual: "missing, as the re-run stopped early".to_owned(),
+                });
+                break;
+            }
+        };
+
+        divergence = verify::first_difference(&recorded, &verify::as_recorded(&actual));
+        if divergence.is_some() {
+            break;
+        }
+
+        ticks += 1;
+    }
+
+    // Stop the world and wait for everything to finish up.
+    let _ = stop_world.send(());
+    drop(snapshots);
+
+    for res in main_coord {
+        res.expect("Panic at shutdown.");
+    }
+
+    if let Some(diff) = divergence {
+        println!("{}", diff);
+        process::exit(1);
+    }
+
+    println!("All {} ticks match the replay.", ticks);
+}
+
+/// Play a robot against each of the reference opponents in turn, one on one in
+/// deterministic mode, and report how it did. The arguments are the robot's
+/// program and, optionally, how many matches to play against each. Matches
+/// are seeded from `SEED` onwards, so the same robot always gets the same
+/// results.
+fn run_benchmark(args: &[String]) {
+    let usage = "Usage: battlebots bench PROGRAM [MATCHES]";
+    let program = args.get(0).expect(usage);
+    let matches: u64 = args.get(1)
+        .map_or(BENCH_MATCHES, |matches| matches.parse().expect("Invalid number of matches"));
+
+    if program == human::PROGRAM {
+        println!("A keyboard-controlled robot can't be benchmarked.");
+        process::exit(1);
+    }
+
+    let mut config = Config::default();
+    config.deterministic = true;
+
+    let first_seed = env::var("SEED")
+        .ok()
+        .map_or(0, |seed| seed.parse().expect("SEED must be a number"));
+    let max_ticks = BENCH_SECONDS * config.ticks_per_second as u64;
+
+    for opponent in native::OPPONENTS {
+        let (mut wins, mut losses, mut draws) = (0, 0, 0);
+        let (mut dealt, mut taken) = (0.0, 0.0);
+
+        for seed in first_seed..first_seed + matches {
+            let header = Header {
+                version: replay::VERSION,
+                config: config.clone(),
+                seed: seed,
+                bots: vec![program.clone(), format!("{}{}", native::PREFIX, opponent)]
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, program)| {
+                        BotInfo {
+                            id: id as u64,
+                            program: program,
+                            team: None,
+                        }
+                    })
+                    .collect(),
+            };
+
+            check_builtins(&header);
+
+            let last = run_headless(&header, max_ticks);
+            let alive: Vec<bool> = last.bots.iter().map(|bot| bot.hit_points > 0.0).collect();
+
+            match (alive[0], alive[1]) {
+                (true, false) => wins += 1,
+                (false, true) => losses += 1,
+                _ => draws += 1,
+            }
+
+            dealt += last.scores[0].damage_dealt;
+            taken += last.scores[0].damage_taken;
+        }
+
+        println!("{:16} {} won, {} lost, {} drawn, {:.1} damage dealt and {:.1} taken per match",
+                 opponent,
+                 wins,
+                 losses,
+                 draws,
+                 dealt / matches as f64,
+                 taken / matches as f64);
+    }
+}
+
+/// Run a match from its header as fast as possible without drawing it, until
+/// at most one robot is left or it has gone on for the given number of ticks.
+/// Returns the last tick's snapshot.
+fn run_headless(header: &Header, max_ticks: u64) -> Snapshot {
+    let ctls = spawn_ctls(header, None);
+
+    let (mut world, tick_lock, stop_world) = World::new(header.config.clone(), ctls);
+    world.set_realtime(false);
+
+    let snapshots = world.observe();
+
+    let mut main_coord = Coordinator::new();
+
+    spawn_world(world, tick_lock, &mut main_coord);
+
+    let mut last = Snapshot::default();
+
+    for snapshot in snapshots.iter() {
+        let alive = snapshot.bots.iter().filter(|bot| bot.hit_points > 0.0).count();
+        let over = alive <= 1 || snapshot.tick + 1 >= max_ticks;
+        last = snapshot;
+
+        if over {
+            break;
+        }
+    }
+
+    // Stop the world and wait for everything to finish up.
+    let _ = stop_world.send(());
+    drop(snapshots);
+
+    for res in main_coord {
+        res.expect("Panic at shutdown.");
+    }
+
+    last
+}
+
+/// Draw ticks of a replay to PNG images in a directory, named after their
+/// ticks. The arguments are the replay file, the directory and the ticks to
+/// draw. Every tick is drawn if none are given.
+fn export_pngs(args: &[String]) {
+    let usage = "Usage: battlebots png FILE DIR [TICK...]";
+    let path = args.get(0).expect(usage);
+    let dir = Path::new(args.get(1).expect(usage));
+    let ticks: Vec<u64> = args[2..]
+        .iter()
+        .map(|tick| tick.parse().expect("Invalid tick"))
+        .collect();
+
+    let reader = Reader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    let header = reader.header().clone();
+    let rasterizer = Rasterizer::new(&header.config, &header.bots, image_width());
+
+    let mut written = 0;
+
+    for snapshot in reader {
+        let snapshot = snapshot.unwrap();
+
+        if ticks.is_empty() || ticks.contains(&snapshot.tick) {
+            let file = File::create(dir.join(format!("tick-{:06}.png", snapshot.tick))).unwrap();
+            raster::write_png(&rasterizer.draw(&snapshot), BufWriter::new(file)).unwrap();
+            written += 1;
+        }
+    }
+
+    println!("Wrote {} images.", written);
+}
+
+/// Draw a whole replay into an animated GIF. The arguments are the replay
+/// file and the GIF file.
+fn export_gif(args: &[String]) {
+    let usage = "Usage: battlebots gif FILE OUT";
+    let path = args.get(0).expect(usage);
+    let out = args.get(1).expect(usage);
+
+    let reader = Reader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    let header = reader.header().clone();
+    let rasterizer = Rasterizer::new(&header.config, &header.bots, image_width());
+
+    let file = BufWriter::new(File::create(out).unwrap());
+    raster::write_gif(file,
+                      &rasterizer,
+                      reader.map(|snapshot| snapshot.unwrap()),
+                      gif_ticks_per_frame(&header.config))
+        .unwrap();
+}
+
+/// Draw a tick of a replay as an SVG image. The arguments are the replay file,
+/// the tick, the SVG file and, optionally, how many ticks of each robot's
+/// trail to draw.
+fn export_svg(args: &[String]) {
+    let usage = "Usage: battlebots svg FILE TICK OUT [TRAIL_TICKS]";
+    let path = args.get(0).expect(usage);
+    let tick: u64 = args.get(1).expect(usage).parse().expect("Invalid tick");
+    let out = args.get(2).expect(usage);
+    let trail_ticks: usize = args.get(3).map_or(0, |ticks| ticks.parse().expect("Invalid trail"));
+
+    let reader = Reader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    let header = reader.header().clone();
+
+    let mut extras = svg::Extras::default();
+    let mut trails: Vec<VecDeque<Vector2>> = vec![VecDeque::new(); header.bots.len()];
+    let mut prev = None;
+
+    for snapshot in reader {
+        let snapshot = snapshot.unwrap();
+
+        for (trail, bot) in trails.iter_mut().zip(&snapshot.bots) {
+            trail.push_back(bot.pos);
+            if trail.len() > trail_ticks {
+                trail.pop_front();
+            }
+        }
+
+        if snapshot.tick < tick {
+            prev = Some(snapshot);
+            continue;
+        }
+
+        if snapshot.tick > tick {
+            break;
+        }
+
+        extras.trails = trails.iter().map(|trail| trail.iter().cloned().collect()).collect();
+        extras.prev_radar_headings = match prev {
+            Some(ref prev) => prev.bots.iter().map(|bot| bot.radar_heading).collect(),
+            None => Vec::new(),
+        };
+
+        let file = BufWriter::new(File::create(out).unwrap());
+        svg::write_svg(file, &header.config, &header.bots, &snapshot, &extras).unwrap();
+
+        println!("Wrote tick {} to {}.", tick, out);
+        return;
+    }
+
+    println!("The replay has no tick {}.", tick);
+    process::exit(1);
+}
+
+/// The width of exported images, from `IMAGE_WIDTH` if it is set.
+fn image_width() -> u32 {
+    env::var("IMAGE_WIDTH")
+        .ok()
+        .map_or(IMAGE_WIDTH, |width| width.parse().expect("IMAGE_WIDTH must be a number"))
+}
+
+/// How many ticks each frame of an exported GIF lasts.
+fn gif_ticks_per_frame(config: &Config) -> u32 {
+    (config.ticks_per_second / GIF_FRAMES_PER_SECOND).max(1)
+}
+
+/// Exit with an error if any robot in the header is meant to be built in, but
+/// there is no built-in robot of its name.
+fn check_builtins(header: &Header) {
+    for bot in &header.bots {
+        if let Err(native::Error::UnknownBot(name)) = native::builtin(&bot.program) {
+            println!("No built-in robot called {}, try one of {}.",
+                     name,
+                     native::NAMES.join(", "));
+            process::exit(1);
+        }
+    }
+}
+
+/// Start a robot process for each robot in the header, placing them randomly
+/// based on the header's seed. Built-in, `.wasm` and `.rhai` robots are hosted
+/// in the simulator, and robots whose program is `human` are driven by the given
+/// keyboard, so there must be one if any are in the match.
+fn spawn_ctls(header: &Header, keyboard: Option<&Keyboard>) -> Vec<Ctl> {
+    let config = &header.config;
+    let mut rng = Isaac64Rng::from_seed(&[header.seed]);
+
+    header.bots
+        .iter()
+        .map(|bot| {
+            let pos = Vector2::new(rng.gen_range(0.0, config.world_size.x),
+                                   rng.gen_range(0.0, config.world_size.y));
+
+            if bot.program == human::PROGRAM {
+                let keyboard = keyboard.expect("A keyboard is needed for human robots");
+                return human::new_ctl(bot.id, pos, config.clone(), keyboard.clone());
+            }
+
+            let builtin = native::builtin(&bot.program)
+                .expect("Built-in robots should be checked before they are started");
+
+            if let Some(controller) = builtin {
+                return native::new_ctl(bot.id, pos, config.clone(), controller);
+            }
+
+            if let Some(ctl) = wasm_ctl(bot, pos, config) {
+                return ctl;
+            }
+
+            if let Some(ctl) = script_ctl(bot, pos, config) {
+                return ctl;
+            }
+
+            let child = Command::new(&bot.program)
                 .stdin(Stdio::piped())
                 .stdout(Stdio::piped())
                 .spawn()
                 .unwrap();
 
-            let pos = Vector2::new(0.0, 0.0);
-
-            Ctl::new(id,
+            Ctl::new(bot.id,
                      pos,
                      config.clone(),
                      child.stdin.unwrap(),
                      child.stdout.unwrap())
         })
-        .collect();
+        .collect()
+}
 
-    println!("Starting the simulation...");
+/// Load the robot if it is a WebAssembly module.
+#[cfg(feature = "wasm")]
+fn wasm_ctl(bot: &BotInfo, pos: Vector2, config: &Config) -> Option<Ctl> {
+    if !bot.program.ends_with(wasm::EXTENSION) {
+        return None;
+    }
 
-    let (world, tick_lock, stop_world) = World::new(config.clone(), ctls);
+    Some(wasm::new_ctl(bot.id, pos, config.clone(), &bot.program)
+        .unwrap_or_else(|err| panic!("Couldn't load {}: {:?}", bot.program, err)))
+}
 
+#[cfg(not(feature = "wasm"))]
+fn wasm_ctl(bot: &BotInfo, _pos: Vector2, _config: &Config) -> Option<Ctl> {
+    if bot.program.ends_with(".wasm") {
+        panic!("Running {} needs the `wasm` feature", bot.program);
+    }
+
+    None
+}
+
+/// Load the robot if it is a Rhai script.
+#[cfg(feature = "script")]
+fn script_ctl(bot: &BotInfo, pos: Vector2, config: &Config) -> Option<Ctl> {
+    if !bot.program.ends_with(script::EXTENSION) {
+        return None;
+    }
+
+    Some(script::new_ctl(bot.id, pos, config.clone(), &bot.program)
+        .unwrap_or_else(|err| panic!("Couldn't load {}: {:?}", bot.program, err)))
+}
+
+#[cfg(not(feature = "script"))]
+fn script_ctl(bot: &BotInfo, _pos: Vector2, _config: &Config) -> Option<Ctl> {
+    if bot.program.ends_with(".rhai") {
+        panic!("Running {} needs the `script` feature", bot.program);
+    }
+
+    None
+}
+
+/// Run the world, and each of its robots, in coordinated threads.
+fn spawn_world(world: World<Ctl>, tick_lock: Arc<TickLock>, main_coord: &mut Coordinator<()>) {
     // the coordinator for the individual robots
     let mut robo_coord = Coordinator::new();
 
@@ -58,13 +662,6 @@ fn main() {
         robo_coord.spawn(move || robo.run(&*tick_lock));
     }
 
-    // This is a synchronised view into the current state of the robots, to give
-    // to the drawing thread.
-    let robos_data = world.robos_data();
-
-    // the coordinator for drawing, world running, and robots
-    let mut main_coord = Coordinator::new();
-
     // Run the world in its own coordinated thread
     {
         let mut world = AssertUnwindSafe(world);
@@ -72,41 +669,6 @@ fn main() {
         main_coord.spawn(move || world.run());
     }
 
-    // Do drawing in its own coordinated thread
-    {
-        main_coord.spawn(AssertUnwindSafe(move || {
-            use glium::DisplayBuild;
-
-            let display = glutin::WindowBuilder::new()
-                .with_vsync()
-                .build_glium()
-                .unwrap();
-
-            let mut draw_state = DrawState::new(&display, robos_data);
-
-            loop {
-                draw_state.update();
-
-                let params = glium::DrawParameters {
-                    multisampling: true,
-                    smooth: Some(glium::Smooth::Nicest),
-                    ..Default::default()
-                };
-
-                let mut target = display.draw();
-                target.clear_color(0.0, 0.0, 0.0, 0.0);
-                draw_state.draw(&mut target, &params).unwrap();
-                target.finish().unwrap();
-
-                for event in display.poll_events() {
-                    if let glutin::Event::Closed = event {
-                        return;
-                    }
-                }
-            }
-        }));
-    }
-
     // Link the coordinated robots to the main thread coordinator
     {
         let robo_coord = AssertUnwindSafe(robo_coord);
@@ -119,21 +681,108 @@ fn main() {
             }
         });
     }
+}
 
-    // Wait for the first of the drawing, world or robots thread to end. If
-    // there are no errors, this will always be the drawing thread (when the
-    // user closes the window).
-    main_coord.next().unwrap().expect("Drawing, world or robots thread panicked");
+/// Play back a recorded match. The arguments are the replay file and,
+/// optionally, the tick to start from.
+fn play_replay(args: &[String]) {
+    let path = args.get(0).expect("Usage: battlebots replay FILE [TICK]");
+    let start_tick = args.get(1).map(|tick| tick.parse().expect("Invalid starting tick"));
 
-    // If the world hasn't already stopped (which it shouldn't have unless there
-    // was a panic), tell it to stop. It will in turn tell each of the robots to
-    // stop.
-    let _ = stop_world.send(());
+    let reader = Reader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    let (mut player, controls) = Player::new(reader).unwrap();
 
-    // Wait for other running threads to finish up.
-    for res in main_coord {
-        res.expect("Panic at shutdown.");
+    if let Some(tick) = start_tick {
+        controls.send(Control::Seek(tick)).unwrap();
     }
 
+    let config = player.header().config.clone();
+    let bots = player.header().bots.clone();
+    let snapshots = player.observe();
+    let header = player.header().clone();
+    let _spectate_server = spectate(&header, player.observe());
+    let player_thread = thread::spawn(move || player.run());
+
+    // Replays are only played back, so there is nothing to reload.
+    view(&config, &bots, snapshots, None, &mut |input| {
+        let control = match input {
+            Input::TogglePause => Control::TogglePause,
+            Input::StepForward => Control::StepForward,
+            Input::StepBack => Control::StepBack,
+            Input::Faster => Control::Faster,
+            Input::Slower => Control::Slower,
+            Input::NextEvent => Control::NextEvent,
+            Input::PrevEvent => Control::PrevEvent,
+            Input::Seek(tick) => Control::Seek(tick),
+            Input::Reload => return,
+        };
+
+        let _ = controls.send(control);
+    });
+
+    let _ = controls.send(Control::Stop);
+    player_thread.join().expect("Playback thread panicked");
+
     println!("Goodbye!");
 }
+
+/// Stream the match to spectators' browsers if the `SPECTATE` environment
+/// variable gives an address to serve the viewer page at.
+fn spectate(header: &Header, snapshots: Receiver<Snapshot>) -> Option<spectate::Server> {
+    env::var("SPECTATE").ok().map(|addr| {
+        let addr = addr.parse().expect("SPECTATE must be an address like 127.0.0.1:8080");
+        let server = spectate::Server::new(addr, header, snapshots).unwrap();
+
+        println!("Spectators can watch at {}", server.url());
+
+        server
+    })
+}
+
+/// Whether the `VIEWER` environment variable asks for the text viewer.
+fn text_viewer() -> bool {
+    env::var("VIEWER").ok().map_or(false, |viewer| viewer == "text")
+}
+
+/// Show the match until the viewer is closed, passing control input to
+/// `handle_input`. The viewer is picked by the `VIEWER` environment variable:
+/// `text` draws in the terminal, at the size (e.g. `60x30`) given by
+/// `VIEW_SIZE` and the frame rate given by `VIEW_RATE`. Anything else opens a
+/// window. A keyboard-controlled robot can only be driven from the window, so
+/// the keyboard is ignored by the text viewer.
+fn view(config: &Config,
+        bots: &[BotInfo],
+        snapshots: Receiver<Snapshot>,
+        keyboard: Option<Keyboard>,
+        handle_input: &mut FnMut(Input)) {
+    let mut viewer: Box<Viewer> = if text_viewer() {
+        let size = env::var("VIEW_SIZE").ok().map_or(TEXT_SIZE, |size| {
+            let mut dims = size.split('x').map(|dim| dim.parse().expect("Invalid VIEW_SIZE"));
+            match (dims.next(), dims.next()) {
+                (Some(width), Some(height)) => (width, height),
+                _ => panic!("VIEW_SIZE must be WIDTHxHEIGHT"),
+            }
+        });
+        let rate = env::var("VIEW_RATE")
+            .ok()
+            .map_or(TEXT_RATE, |rate| rate.parse().expect("VIEW_RATE must be a number"));
+
+        println!("{}", tui::TEXT_COMMANDS);
+
+        Box::new(TextViewer::new(snapshots, config, bots, size, rate))
+    } else {
+        println!("{}", VIEW_KEYS);
+
+        let viewer = GlViewer::new(snapshots, config, bots);
+
+        match keyboard {
+            Some(keyboard) => {
+                println!("{}", HUMAN_KEYS);
+                Box::new(viewer.with_keyboard(keyboard))
+            }
+            None => Box::new(viewer),
+        }
+    };
+
+    viewer.run(handle_input);
+}
@@ -0,0 +1,217 @@
+//! Playing back recorded matches, in place of a running world.
+
+use replay::{Header, Reader, Error};
+use snapshot::*;
+use world::{MIN_SPEED, MAX_SPEED};
+
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+use std::thread;
+use std::time::Duration;
+
+/// A command which can be sent to a running `Player`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    /// Pause if playing, or play if paused.
+    TogglePause,
+
+    /// Pause and move forward a single tick.
+    StepForward,
+
+    /// Pause and move back a single tick.
+    StepBack,
+
+    /// Double the playback speed, up to `MAX_SPEED`.
+    Faster,
+
+    /// Halve the playback speed, down to `MIN_SPEED`.
+    Slower,
+
+    /// Jump to the given tick, or the last tick if it is past the end.
+    Seek(u64),
+
+    /// Jump to the next tick in which a robot was hit or died.
+    NextEvent,
+
+    /// Jump to the previous tick in which a robot was hit or died.
+    PrevEvent,
+
+    /// Stop playing back.
+    Stop,
+}
+
+/// Plays back a recorded match by updating a snapshot in the same way as a
+/// running `World`, so it can be drawn in the same way.
+pub struct Player {
+    header: Header,
+    snapshots: Vec<Snapshot>,
+    robos_data: Arc<Mutex<Snapshot>>,
+    observers: Vec<Sender<Snapshot>>,
+    control_receiver: Receiver<Control>,
+
+    current: usize,
+    paused: bool,
+    speed: f64,
+}
+
+impl Player {
+    /// Load a whole replay into memory, so that it can be played back in any
+    /// order. Returns the player and a sender to control it with.
+    pub fn new<R: BufRead>(reader: Reader<R>) -> Result<(Self, Sender<Control>), Error> {
+        let header = reader.header().clone();
+        let snapshots: Vec<Snapshot> = try!(reader.collect());
+
+        let initial = snapshots.first().cloned().unwrap_or_else(Snapshot::default);
+
+        let (control_sender, control_receiver) = channel();
+
+        Ok((Player {
+            header: header,
+            robos_data: Arc::new(Mutex::new(initial)),
+            snapshots: snapshots,
+            observers: Vec::new(),
+            control_receiver: control_receiver,
+
+            current: 0,
+            paused: false,
+            speed: 1.0,
+        },
+            control_sender))
+    }
+
+    /// The header of the replay being played.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Get the mutex-protected snapshot of the current tick so that it can be
+    /// drawn.
+    pub fn robos_data(&self) -> Arc<Mutex<Snapshot>> {
+        self.robos_data.clone()
+    }
+
+    /// Receive every snapshot shown from now on, starting with the current
+    /// one. Dropping the receiver stops the snapshots.
+    pub fn observe(&mut self) -> Receiver<Snapshot> {
+        let (sender, receiver) = channel();
+
+        if let Some(snapshot) = self.snapshots.get(self.current) {
+            let _ = sender.send(snapshot.clone());
+        }
+
+        self.observers.push(sender);
+        receiver
+    }
+
+    /// Synchronously plays back the match. When the end is reached, playback
+    /// pauses on the last tick.
+    ///
+    /// Run until a `Control::Stop` is received or the sender hangs up.
+    pub fn run(&mut self) {
+        let tick_dur = self.header.config.tick_seconds();
+
+        loop {
+            loop {
+                match self.control_receiver.try_recv() {
+                    Ok(Control::Stop) => return,
+                    Ok(control) => self.apply(control),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+
+            if !self.paused {
+                if self.current + 1 < self.snapshots.len() {
+                    let next = self.current + 1;
+                    self.show(next);
+                } else {
+                    self.paused = true;
+                    println!("End of replay at tick {}", self.current_tick());
+                }
+            }
+
+            thread::sleep(float_duration(tick_dur / self.speed));
+        }
+    }
+
+    fn apply(&mut self, control: Control) {
+        use self::Control::*;
+
+        let last = self.snapshots.len().saturating_sub(1);
+
+        match control {
+            TogglePause => self.paused = !self.paused,
+
+            StepForward => {
+                self.paused = true;
+                let next = (self.current + 1).min(last);
+                self.show(next);
+            }
+
+            StepBack => {
+                self.paused = true;
+                let prev = self.current.saturating_sub(1);
+                self.show(prev);
+            }
+
+            Faster => self.speed = (self.speed * 2.0).min(MAX_SPEED),
+            Slower => self.speed = (self.speed / 2.0).max(MIN_SPEED),
+
+            Seek(tick) => {
+                let index = self.snapshots
+                    .iter()
+                    .position(|snapshot| snapshot.tick >= tick)
+                    .unwrap_or(last);
+                self.show(index);
+            }
+
+            NextEvent => {
+                let found = self.snapshots
+                    .iter()
+                    .enumerate()
+                    .skip(self.current + 1)
+                    .find(|&(_, snapshot)| is_eventful(snapshot))
+                    .map(|(index, _)| index);
+
+                if let Some(index) = found {
+                    self.show(index);
+                }
+            }
+
+            PrevEvent => {
+                if let Some(index) = self.snapshots[..self.current].iter().rposition(is_eventful) {
+                    self.show(index);
+                }
+            }
+
+            Stop => {}
+        }
+    }
+
+    /// Show the snapshot at the given index.
+    fn show(&mut self, index: usize) {
+        if let Some(snapshot) = self.snapshots.get(index) {
+            self.current = index;
+            self.observers.retain(|observer| observer.send(snapshot.clone()).is_ok());
+            *self.robos_data.lock().unwrap() = snapshot.clone();
+        }
+    }
+
+    fn current_tick(&self) -> u64 {
+        self.snapshots.get(self.current).map_or(0, |snapshot| snapshot.tick)
+    }
+}
+
+/// Whether a robot was hit or died during the snapshot's tick.
+fn is_eventful(snapshot: &Snapshot) -> bool {
+    snapshot.events.iter().any(|event| match *event {
+        Event::Hit { .. } |
+        Event::Death { .. } => true,
+        _ => false,
+    })
+}
+
+fn float_duration(secs: f64) -> Duration {
+    Duration::new(secs as u64, (secs.fract() * 1_000_000_000.0) as u32)
+}
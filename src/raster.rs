@@ -0,0 +1,330 @@
+//! Drawing snapshots of the world to images on the CPU, for sharing matches
+//! without a GPU or a window. Robots are drawn with the same shapes and
+//! colours as `DrawState`.
+
+use math::*;
+use rpc::BotState;
+use config::Config;
+use snapshot::Snapshot;
+use replay::BotInfo;
+use render::{self, BODY_SHAPE, RADAR_SHAPE, GUN_SHAPE, BULLET_SHAPE, GUN_SHADE, BAR_WIDTH,
+             BAR_HEIGHT, BAR_OFFSET, BAR_BACK_COLOR, HIT_POINTS_COLOR, SHOOT_POWER_COLOR,
+             BORDER_COLOR};
+
+use png;
+use gif;
+use gif::SetParameter;
+
+use std::f64;
+use std::io::{self, Write};
+
+/// Where each pixel is sampled for anti-aliasing, as offsets from its top
+/// left corner.
+const SAMPLES: [(f64, f64); 4] = [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)];
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Png(png::EncodingError),
+}
+
+/// An RGB image with 8 bits per channel, stored row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Draws snapshots of a match to images.
+#[derive(Debug, Clone)]
+pub struct Rasterizer {
+    config: Config,
+
+    /// The colour of each robot which hasn't chosen its own, in world order.
+    base_colors: Vec<[f32; 3]>,
+
+    /// The number of pixels per world unit.
+    scale: f64,
+
+    width: u32,
+    height: u32,
+}
+
+impl Image {
+    fn new(width: u32, height: u32) -> Self {
+        Image {
+            width: width,
+            height: height,
+            pixels: vec![0; width as usize * height as usize * 3],
+        }
+    }
+
+    /// Mix a colour into a pixel by the given amount, between 0 and 1.
+    fn blend(&mut self, x: u32, y: u32, color: [f32; 4], amount: f32) {
+        let amount = amount * color[3];
+        let index = (y as usize * self.width as usize + x as usize) * 3;
+
+        for (pixel, &c) in self.pixels[index..index + 3].iter_mut().zip(&color[..3]) {
+            let mixed = *pixel as f32 * (1.0 - amount) + c.max(0.0).min(1.0) * 255.0 * amount;
+            *pixel = mixed.round() as u8;
+        }
+    }
+}
+
+impl Rasterizer {
+    /// Create a rasterizer for a match, drawing images the given number of
+    /// pixels across. The height follows from the shape of the world. The
+    /// robots' infos must be in world order.
+    pub fn new(config: &Config, bots: &[BotInfo], width: u32) -> Self {
+        let width = width.max(1);
+        let scale = width as f64 / config.world_size.x;
+
+        Rasterizer {
+            config: config.clone(),
+            base_colors: bots.iter()
+                .enumerate()
+                .map(|(i, bot)| render::base_color(i, bot))
+                .collect(),
+            scale: scale,
+            width: width,
+            height: (config.world_size.y * scale).round().max(1.0) as u32,
+        }
+    }
+
+    /// Draw the arena, robots, bullets and bars of a snapshot, in the same
+    /// order as the window.
+    pub fn draw(&self, snapshot: &Snapshot) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        self.draw_border(&mut image);
+
+        let colors: Vec<_> = snapshot.bots
+            .iter()
+            .enumerate()
+            .map(|(i, bot)| self.bot_color(i, bot))
+            .collect();
+
+        for (bot, &color) in snapshot.bots.iter().zip(&colors) {
+            self.fill_part(&mut image, &BODY_SHAPE, bot.heading, bot.pos, 1.0, color);
+        }
+
+        for (bot, &color) in snapshot.bots.iter().zip(&colors) {
+            let shaded = [color[0] * GUN_SHADE, color[1] * GUN_SHADE, color[2] * GUN_SHADE, 1.0];
+            self.fill_part(&mut image, &GUN_SHAPE, bot.gun_heading, bot.pos, 1.0, shaded);
+        }
+
+        for (bot, &color) in snapshot.bots.iter().zip(&colors) {
+            let light = render::lighten(color);
+            self.fill_part(&mut image, &RADAR_SHAPE, bot.radar_heading, bot.pos, 1.0, light);
+        }
+
+        for bullet in &snapshot.bullets {
+            let color = colors.get(bullet.owner).map_or([1.0; 4], |&color| render::lighten(color));
+            let size = render::bullet_size(bullet.power) as f64;
+            self.fill_part(&mut image, &BULLET_SHAPE, 0.0, bullet.pos, size, color);
+        }
+
+        let config = &self.config;
+
+        for bot in &snapshot.bots {
+            self.fill_bar(&mut image, bot.pos, 0, 1.0, BAR_BACK_COLOR);
+            self.fill_bar(&mut image, bot.pos, 1, 1.0, BAR_BACK_COLOR);
+        }
+
+        for bot in &snapshot.bots {
+            self.fill_bar(&mut image,
+                          bot.pos,
+                          0,
+                          bot.hit_points / config.max_hit_points,
+                          HIT_POINTS_COLOR);
+            self.fill_bar(&mut image,
+                          bot.pos,
+                          1,
+                          bot.shoot_power / config.max_shoot_power,
+                          SHOOT_POWER_COLOR);
+        }
+
+        image
+    }
+
+    fn bot_color(&self, index: usize, bot: &BotState) -> [f32; 4] {
+        let base = self.base_colors.get(index).cloned().unwrap_or([1.0, 1.0, 1.0]);
+        render::bot_color(bot, base)
+    }
+
+    /// Convert a world position to a position in pixels from the top left.
+    fn to_pixels(&self, pos: Vector2) -> Vector2 {
+        Vector2::new(pos.x * self.scale, (self.config.world_size.y - pos.y) * self.scale)
+    }
+
+    /// The arena's border is a line around the edge of the image.
+    fn draw_border(&self, image: &mut Image) {
+        let (width, height) = (image.width, image.height);
+
+        for x in 0..width {
+            image.blend(x, 0, BORDER_COLOR, 1.0);
+            image.blend(x, height - 1, BORDER_COLOR, 1.0);
+        }
+
+        for y in 0..height {
+            image.blend(0, y, BORDER_COLOR, 1.0);
+            image.blend(width - 1, y, BORDER_COLOR, 1.0);
+        }
+    }
+
+    /// Fill one of the shapes from `render`, scaled, rotated to a heading and
+    /// moved to a position in the world.
+    fn fill_part(&self,
+                 image: &mut Image,
+                 shape: &[[f32; 2]],
+                 heading: f64,
+                 pos: Vector2,
+                 size: f64,
+                 color: [f32; 4]) {
+        let (sin, cos) = heading.sin_cos();
+
+        let points: Vec<_> = shape.iter()
+            .map(|point| {
+                let (x, y) = (point[0] as f64 * size, point[1] as f64 * size);
+                self.to_pixels(pos + Vector2::new(x * cos - y * sin, x * sin + y * cos))
+            })
+            .collect();
+
+        fill_convex(image, &points, color);
+    }
+
+    /// Fill a bar above a robot, as in the window. Row 0 is nearest the robot.
+    fn fill_bar(&self, image: &mut Image, pos: Vector2, row: u32, fraction: f64, color: [f32; 4]) {
+        let fraction = fraction.max(0.0).min(1.0);
+        if fraction == 0.0 {
+            return;
+        }
+
+        let left = pos.x - BAR_WIDTH as f64 * 0.5;
+        let right = left + BAR_WIDTH as f64 * fraction;
+        let bottom = pos.y + BAR_OFFSET as f64 + row as f64 * BAR_HEIGHT as f64 * 1.5;
+        let top = bottom + BAR_HEIGHT as f64;
+
+        let points = [self.to_pixels(Vector2::new(left, bottom)),
+                      self.to_pixels(Vector2::new(left, top)),
+                      self.to_pixels(Vector2::new(right, top)),
+                      self.to_pixels(Vector2::new(right, bottom))];
+
+        fill_convex(image, &points, color);
+    }
+}
+
+/// Fill a convex polygon, given in pixels from the top left, anti-aliasing its
+/// edges by sampling each pixel several times.
+fn fill_convex(image: &mut Image, points: &[Vector2], color: [f32; 4]) {
+    if points.len() < 3 {
+        return;
+    }
+
+    let min_x = points.iter().fold(f64::INFINITY, |min, p| min.min(p.x)).floor().max(0.0);
+    let min_y = points.iter().fold(f64::INFINITY, |min, p| min.min(p.y)).floor().max(0.0);
+    let max_x = points.iter().fold(-f64::INFINITY, |max, p| max.max(p.x)).ceil();
+    let max_y = points.iter().fold(-f64::INFINITY, |max, p| max.max(p.y)).ceil();
+
+    let max_x = max_x.min(image.width as f64);
+    let max_y = max_y.min(image.height as f64);
+
+    let inside = |x: f64, y: f64| {
+        let mut positive = false;
+        let mut negative = false;
+
+        for (i, a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            let cross = (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x);
+
+            positive = positive || cross > 0.0;
+            negative = negative || cross < 0.0;
+        }
+
+        !(positive && negative)
+    };
+
+    let mut y = min_y;
+    while y < max_y {
+        let mut x = min_x;
+        while x < max_x {
+            let covered = SAMPLES.iter().filter(|&&(dx, dy)| inside(x + dx, y + dy)).count();
+
+            if covered > 0 {
+                image.blend(x as u32, y as u32, color, covered as f32 / SAMPLES.len() as f32);
+            }
+
+            x += 1.0;
+        }
+
+        y += 1.0;
+    }
+}
+
+/// Write an image as a PNG.
+pub fn write_png<W: Write>(image: &Image, writer: W) -> Result<(), Error> {
+    let mut encoder = png::Encoder::new(writer, image.width, image.height);
+    encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+
+    let mut writer = try!(encoder.write_header().map_err(Error::Png));
+    writer.write_image_data(&image.pixels).map_err(Error::Png)
+}
+
+/// Writes images as the frames of a looping animated GIF.
+pub struct GifWriter<W: Write> {
+    encoder: gif::Encoder<W>,
+
+    /// How long each frame is shown, in hundredths of a second.
+    delay: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Start a GIF of the given size, showing the given number of frames a
+    /// second. Every frame must be the same size. Frames are shown for at least
+    /// two hundredths of a second, as browsers slow down anything shorter.
+    pub fn new(writer: W, width: u32, height: u32, frames_per_second: f64) -> Result<Self, Error> {
+        let mut encoder = try!(gif::Encoder::new(writer, width as u16, height as u16, &[])
+            .map_err(Error::Io));
+        try!(encoder.set(gif::Repeat::Infinite).map_err(Error::Io));
+
+        Ok(GifWriter {
+            encoder: encoder,
+            delay: (100.0 / frames_per_second).round().max(2.0).min(u16::max_value() as f64) as u16,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &Image) -> Result<(), Error> {
+        let mut frame =
+            gif::Frame::from_rgb(image.width as u16, image.height as u16, &image.pixels);
+        frame.delay = self.delay;
+
+        self.encoder.write_frame(&frame).map_err(Error::Io)
+    }
+}
+
+/// Draw every `ticks_per_frame`th snapshot into an animated GIF, played back
+/// as close to the match's real speed as the frame delays of GIFs allow.
+pub fn write_gif<W, I>(writer: W,
+                       rasterizer: &Rasterizer,
+                       snapshots: I,
+                       ticks_per_frame: u32)
+                       -> Result<(), Error>
+    where W: Write,
+          I: IntoIterator<Item = Snapshot>
+{
+    let ticks_per_frame = ticks_per_frame.max(1);
+    let frames_per_second = rasterizer.config.ticks_per_second as f64 / ticks_per_frame as f64;
+
+    let mut gif = try!(GifWriter::new(writer,
+                                      rasterizer.width,
+                                      rasterizer.height,
+                                      frames_per_second));
+
+    for snapshot in snapshots {
+        if snapshot.tick % ticks_per_frame as u64 == 0 {
+            try!(gif.write_frame(&rasterizer.draw(&snapshot)));
+        }
+    }
+
+    Ok(())
+}
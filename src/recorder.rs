@@ -0,0 +1,49 @@
+//! Recording matches to replay files as they run.
+
+use replay::{Header, Writer, Error};
+use snapshot::Snapshot;
+
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+/// Records each tick's snapshot to a replay. Serialisation and writing happen
+/// on a separate thread so that recording doesn't hold up the simulation.
+pub struct Recorder {
+    join_handle: Option<thread::JoinHandle<Result<(), Error>>>,
+}
+
+impl Recorder {
+    /// Start recording snapshots from the receiver (usually from
+    /// `World::observe`) to the given writer. The header is written straight
+    /// away, on the calling thread.
+    pub fn new<W>(writer: W, header: &Header, receiver: Receiver<Snapshot>) -> Result<Self, Error>
+        where W: Write + Send + 'static
+    {
+        let mut writer = try!(Writer::new(writer, header));
+
+        let join_handle = thread::spawn(move || {
+            for snapshot in receiver {
+                try!(writer.write(&snapshot));
+            }
+
+            writer.flush()
+        });
+
+        Ok(Recorder { join_handle: Some(join_handle) })
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Wait for the writing thread so that nothing queued is lost. It
+        // finishes once the sender of snapshots hangs up.
+        if let Some(join_handle) = self.join_handle.take() {
+            match join_handle.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => println!("Error while recording replay: {:?}", err),
+                Err(_) => println!("Replay recording thread panicked"),
+            }
+        }
+    }
+}
@@ -3,28 +3,317 @@
 
 use math::*;
 use rpc::*;
+use config::Config;
+use physics::duration_float;
+use snapshot::{Diagnostics, Event as WorldEvent, Score, Snapshot};
+use replay::BotInfo;
+use view::{Input, Viewer};
+use ctl::human::Keyboard;
+use svg;
 
 use render_utils::*;
+use render_utils::font::GLYPH_HEIGHT;
+use render_utils::text::{TextRenderer, LINE_HEIGHT};
 use glium::{Surface, DrawParameters, DrawError};
 use glium::index::PrimitiveType;
 use glium::uniforms::{EmptyUniforms, UniformsStorage};
 use glium::backend::Facade;
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::glutin;
+use glium::glutin::{Event, ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode};
 
+use cgmath;
 use cgmath::prelude::*;
-use cgmath::{Vector3, Matrix3, Matrix4, Quaternion, rad};
+use cgmath::{Vector3, Vector4, Matrix3, Matrix4, Quaternion, rad};
 
-use std::sync::{Arc, Mutex};
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
+use std::f64::consts::PI;
+use std::fs::File;
+use std::mem;
+use std::io::BufWriter;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
 
 type Data =
     InstancedData<Vertex, Attr, <GlobalUniforms as IntoUniforms>::IntoUniforms>;
 
+/// The most bullets that can be drawn per robot.
+const MAX_BULLETS_PER_BOT: usize = 32;
+
+/// Half the length and width of a robot's body.
+const BODY_HALF_LENGTH: f32 = 0.6;
+const BODY_HALF_WIDTH: f32 = 0.4;
+
+/// The outlines of a robot's parts, relative to its centre when facing along
+/// the x axis. They are shared with the software renderer, so that its images
+/// look like the window.
+pub const BODY_SHAPE: [[f32; 2]; 4] = [[-BODY_HALF_LENGTH, -BODY_HALF_WIDTH],
+                                       [-BODY_HALF_LENGTH, BODY_HALF_WIDTH],
+                                       [BODY_HALF_LENGTH, BODY_HALF_WIDTH],
+                                       [BODY_HALF_LENGTH, -BODY_HALF_WIDTH]];
+pub const RADAR_SHAPE: [[f32; 2]; 3] = [[0.0, 0.0], [0.3, 0.3], [0.3, -0.3]];
+pub const GUN_SHAPE: [[f32; 2]; 4] = [[0.0, -0.06], [0.0, 0.06], [0.8, 0.06], [0.8, -0.06]];
+
+/// A bullet is a diamond of this shape, scaled by `bullet_size`.
+pub const BULLET_SHAPE: [[f32; 2]; 4] = [[-1.0, 0.0], [0.0, 1.0], [1.0, 0.0], [0.0, -1.0]];
+
+/// How much darker a robot's gun is than its body.
+pub const GUN_SHADE: f32 = 0.5;
+
+/// The size of health and shoot power bars, and how far above the robot they
+/// are drawn.
+pub const BAR_WIDTH: f32 = 1.2;
+pub const BAR_HEIGHT: f32 = 0.12;
+pub const BAR_OFFSET: f32 = 0.8;
+
+/// The colours of the bars' backgrounds, and of each bar.
+pub const BAR_BACK_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+pub const HIT_POINTS_COLOR: [f32; 4] = [0.0, 0.8, 0.0, 1.0];
+pub const SHOOT_POWER_COLOR: [f32; 4] = [0.9, 0.7, 0.0, 1.0];
+
+/// The colour of the arena's border.
+pub const BORDER_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+
+/// The most vertices used for robots' debug drawings. Drawings beyond this are
+/// not shown.
+const MAX_DEBUG_VERTICES: usize = 65536;
+
+/// The number of line segments used to draw a debug circle.
+const CIRCLE_SEGMENTS: usize = 24;
+
+/// The half-width of the cross marking a debug point, in world units.
+const POINT_SIZE: f64 = 0.15;
+
+/// The most vertices used for trails, and for other effects. Any more are not
+/// drawn.
+const MAX_TRAIL_VERTICES: usize = 32768;
+const MAX_EFFECT_VERTICES: usize = 32768;
+
+/// How long each robot's trail is, in ticks.
+const TRAIL_LENGTH: usize = 60;
+
+/// How long each kind of effect lasts, in seconds of simulated time.
+const MUZZLE_FLASH_SECONDS: f64 = 0.1;
+const SPARKS_SECONDS: f64 = 0.25;
+const EXPLOSION_SECONDS: f64 = 0.75;
+const SCAN_FLASH_SECONDS: f64 = 0.2;
+
+/// The distance from a robot's centre to the end of its gun.
+const GUN_LENGTH: f64 = 0.8;
+
+/// The most vertices used for radar sweeps. Any more are not drawn.
+const MAX_SWEEP_VERTICES: usize = 65536;
+
+/// The opacity of radar sweeps, and of their leading edges.
+const SWEEP_ALPHA: f32 = 0.12;
+const SWEEP_EDGE_ALPHA: f32 = 0.4;
+
+/// The colour used to highlight the selected robot and its bullets.
+const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 1.0, 0.4, 1.0];
+
+/// The number of recent responses, debug prints and strikes kept for each
+/// robot, for the inspect panel.
+const HISTORY_LENGTH: usize = 10;
+
+/// The longest line shown in the inspect panel, in characters.
+const PANEL_LINE_LENGTH: usize = 36;
+
+/// How many window pixels across each font pixel is drawn, for robot labels and
+/// for the scoreboard and status line.
+const LABEL_SCALE: f32 = 1.0;
+const HUD_SCALE: f32 = 2.0;
+
+/// The most robots listed on the scoreboard.
+const SCOREBOARD_ROWS: usize = 20;
+
+/// The gap between the edge of the window and the scoreboard or status line,
+/// in pixels.
+const HUD_MARGIN: f32 = 8.0;
+
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const DEAD_TEXT_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const FOLLOWED_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 0.4, 1.0];
+const HUD_BACK_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+/// The instance colour for anything drawn in its vertices' own colours.
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// The colour of robots which are out of the match.
+pub const DEAD_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+
+/// A short-lived visual effect, started by an event in the world. Effects age
+/// with simulated time rather than real time, so that they look the same live
+/// and in replays, and freeze while paused.
+#[derive(Debug, Clone)]
+struct Effect {
+    kind: EffectKind,
+    pos: Vector2,
+
+    /// The direction the effect points in, in radians.
+    heading: f64,
+
+    color: [f32; 4],
+    start_tick: u64,
+
+    /// Varies the shape of the effect. This is derived from the event, so the
+    /// same event always looks the same.
+    seed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EffectKind {
+    /// From the end of a robot's gun when it shoots.
+    MuzzleFlash,
+
+    /// Where a bullet hits a robot.
+    Sparks,
+
+    /// Where a robot dies.
+    Explosion,
+
+    /// Around a robot which was scanned by another's radar.
+    ScanFlash,
+}
+
+impl EffectKind {
+    fn seconds(self) -> f64 {
+        match self {
+            EffectKind::MuzzleFlash => MUZZLE_FLASH_SECONDS,
+            EffectKind::Sparks => SPARKS_SECONDS,
+            EffectKind::Explosion => EXPLOSION_SECONDS,
+            EffectKind::ScanFlash => SCAN_FLASH_SECONDS,
+        }
+    }
+}
+
+/// What the scoreboard is ordered by, from highest to lowest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    HitPoints,
+    Kills,
+    Damage,
+}
+
+impl SortKey {
+    /// The key after this one, cycling back to the first.
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::HitPoints => SortKey::Kills,
+            SortKey::Kills => SortKey::Damage,
+            SortKey::Damage => SortKey::HitPoints,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortKey::HitPoints => "hit points",
+            SortKey::Kills => "kills",
+            SortKey::Damage => "damage",
+        }
+    }
+
+    fn value(self, bot: &BotState, score: &Score) -> f64 {
+        match self {
+            SortKey::HitPoints => bot.hit_points,
+            SortKey::Kills => score.kills as f64,
+            SortKey::Damage => score.damage_dealt,
+        }
+    }
+}
+
 /// Encapsulates state required for drawing the world and robots.
 pub struct DrawState {
     body_data: Data,
     radar_data: Data,
     gun_data: Data,
+    bullet_data: Data,
+    bar_back_data: Data,
+    hit_points_data: Data,
+    shoot_power_data: Data,
+    border_data: Data,
+    debug_data: DynamicData<LineVertex>,
+    trail_data: DynamicData<LineVertex>,
+    effect_data: DynamicData<LineVertex>,
+    sweep_data: DynamicData<LineVertex>,
+    sweep_edge_data: DynamicData<LineVertex>,
+    text: TextRenderer,
+
+    /// The matrix from world to window coordinates for this frame, for
+    /// drawing debug shapes.
+    global_matrix: [[f32; 4]; 4],
+
+    /// The robots whose debug drawings are not shown.
+    debug_hidden: HashSet<usize>,
+
+    /// Each robot's body transform for this frame, for picking robots with the
+    /// mouse.
+    body_matrices: Vec<Matrix4<f32>>,
+
+    /// The robot shown in the inspect panel, if any.
+    selected: Option<usize>,
+
+    /// Each robot's recent responses, debug prints and strikes, oldest first.
+    histories: Vec<VecDeque<String>>,
+
+    /// Each robot's positions over the last few ticks, oldest first.
+    trails: Vec<VecDeque<Vector2>>,
+
+    effects: Vec<Effect>,
+
+    /// Each robot's radar heading on the tick before the current snapshot, to
+    /// show the arc swept since.
+    prev_radar_headings: Vec<f64>,
+    show_sweeps: bool,
+
+    /// The text drawn above each robot, in world order.
+    labels: Vec<String>,
+    bots: Vec<BotInfo>,
 
-    bots: Arc<Mutex<Vec<BotState>>>,
+    /// The colour of each robot which hasn't chosen its own, from its team or
+    /// its place in the world.
+    base_colors: Vec<[f32; 3]>,
+    show_labels: bool,
+    show_scoreboard: bool,
+    sort_key: SortKey,
+
+    /// Frames drawn and the tick shown since `rate_start`, for measuring the
+    /// frame rate and simulation speed about once a second.
+    frames: u32,
+    rate_start: Instant,
+    rate_start_tick: u64,
+    fps: f64,
+    sim_speed: f64,
+
+    camera: Camera,
+    config: Config,
+
+    snapshots: Receiver<Snapshot>,
+
+    /// The latest snapshot received.
+    current: Snapshot,
+}
+
+/// The view of the world shown in the window. By default the whole world is
+/// fitted to the window. It can be panned by dragging with the mouse, zoomed
+/// with the scroll wheel, and locked onto a robot with tab.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    world_size: Vector2,
+    window_size: (u32, u32),
+
+    /// The world position at the centre of the window.
+    centre: Vector2,
+
+    /// How far the camera is zoomed in, relative to fitting the whole world.
+    zoom: f64,
+
+    /// The index of the robot the camera is locked onto, if any.
+    following: Option<usize>,
+
+    /// The last known cursor position, in pixels from the top left.
+    cursor: (i32, i32),
+    dragging: bool,
 }
 
 /// Why does `cgmath` not provide this?
@@ -41,105 +330,1263 @@ fn mat_3_to_4<S: Copy + Zero + One>(mat: Matrix3<S>) -> Matrix4<S> {
 }
 
 impl DrawState {
-    pub fn new<F>(display: &F, bots: Arc<Mutex<Vec<BotState>>>) -> Self
+    /// Create the drawing state for a world, which draws the latest of the
+    /// given snapshots. Every snapshot should be sent, so that none of the
+    /// events in them are missed. The robots' infos are used to label them,
+    /// and must be in world order.
+    pub fn new<F>(display: &F, snapshots: Receiver<Snapshot>, config: &Config, bots: &[BotInfo])
+                  -> Self
         where F: Facade
     {
-        let num_bots = {
-            bots.lock().unwrap().len()
-        };
+        let num_bots = bots.len();
 
-        // TODO: Scale based on configuration.
+        let camera = Camera::new(config.world_size,
+                                 display.get_context().get_framebuffer_dimensions());
+
+        // Robots' parts are coloured by each instance's colour. The gun is
+        // drawn as a darker shade of it.
+        let shape_vertices = |shape: &[[f32; 2]], color: [f32; 4]| -> Vec<Vertex> {
+            shape.iter().map(|&pos| Vertex::new(pos, color)).collect()
+        };
 
         let body = DataBuilder {
-            vertices: vec![
-                Vertex::new([-0.6, -0.4], [1.0, 0.0, 0.0, 1.0]),
-                Vertex::new([-0.6, 0.4], [1.0, 0.0, 0.0, 1.0]),
-                Vertex::new([0.6, 0.4], [1.0, 0.0, 0.0, 1.0]),
-                Vertex::new([0.6, -0.4], [1.0, 0.0, 0.0, 1.0]),
-            ],
+            vertices: shape_vertices(&BODY_SHAPE, WHITE),
             indices: vec![0, 1, 2, 0, 2, 3],
-            uniforms: GlobalUniforms { global_matrix: Matrix4::from_scale(0.1).into() },
+            uniforms: GlobalUniforms { global_matrix: Matrix4::identity().into() },
             vshader_src: include_str!("bin/vshader.glsl"),
             fshader_src: include_str!("bin/fshader.glsl"),
         };
 
         let radar = DataBuilder {
-            vertices: vec![
-                Vertex::new([0.0, 0.0], [0.0, 1.0, 0.0, 1.0]),
-                Vertex::new([0.3, 0.3], [0.0, 1.0, 0.0, 1.0]),
-                Vertex::new([0.3, -0.3], [0.0, 1.0, 0.0, 1.0]),
-            ],
+            vertices: shape_vertices(&RADAR_SHAPE, WHITE),
             indices: vec![0, 1, 2],
             ..body.clone()
         };
 
         let gun = DataBuilder {
+            vertices: shape_vertices(&GUN_SHAPE, [GUN_SHADE, GUN_SHADE, GUN_SHADE, 1.0]),
+            ..body.clone()
+        };
+
+        // A small diamond, scaled for each bullet by its power and coloured
+        // like a lighter version of its owner.
+        let bullet = DataBuilder {
+            vertices: shape_vertices(&BULLET_SHAPE, WHITE),
+            ..body.clone()
+        };
+
+        // Bars extend to the right from the origin, and are scaled
+        // horizontally for each robot by how full they are.
+        let bar_vertices = |color| {
+            vec![
+                Vertex::new([0.0, 0.0], color),
+                Vertex::new([0.0, BAR_HEIGHT], color),
+                Vertex::new([BAR_WIDTH, BAR_HEIGHT], color),
+                Vertex::new([BAR_WIDTH, 0.0], color),
+            ]
+        };
+
+        let bar_back = DataBuilder {
+            vertices: bar_vertices(BAR_BACK_COLOR),
+            ..body.clone()
+        };
+
+        let hit_points = DataBuilder {
+            vertices: bar_vertices(HIT_POINTS_COLOR),
+            ..body.clone()
+        };
+
+        let shoot_power = DataBuilder {
+            vertices: bar_vertices(SHOOT_POWER_COLOR),
+            ..body.clone()
+        };
+
+        let (w, h) = (config.world_size.x as f32, config.world_size.y as f32);
+        let border = DataBuilder {
             vertices: vec![
-                Vertex::new([ 0.0, -0.06,], [0.0, 0.0, 1.0, 1.0]),
-                Vertex::new([ 0.0, 0.06, ], [0.0, 0.0, 1.0, 1.0]),
-                Vertex::new([ 0.8, 0.06, ], [0.0, 0.0, 1.0, 1.0]),
-                Vertex::new([ 0.8, -0.06,], [0.0, 0.0, 1.0, 1.0]),
+                Vertex::new([0.0, 0.0], BORDER_COLOR),
+                Vertex::new([0.0, h], BORDER_COLOR),
+                Vertex::new([w, h], BORDER_COLOR),
+                Vertex::new([w, 0.0], BORDER_COLOR),
             ],
+            indices: vec![0, 1, 2, 3],
             ..body.clone()
         };
 
         let prim_type = PrimitiveType::TriangleStrip;
+        let quads = PrimitiveType::TrianglesList;
+
+        let mut border_data = border.build_instanced(display, 1, PrimitiveType::LineLoop).unwrap();
+        border_data.update_instances(vec![Attr::new(Matrix4::identity(), WHITE)]).unwrap();
 
         DrawState {
             body_data: body.build_instanced(display, num_bots, prim_type).unwrap(),
             radar_data: radar.build_instanced(display, num_bots, prim_type).unwrap(),
             gun_data: gun.build_instanced(display, num_bots, prim_type).unwrap(),
+            bullet_data: bullet.build_instanced(display, num_bots * MAX_BULLETS_PER_BOT, quads)
+                .unwrap(),
+            bar_back_data: bar_back.build_instanced(display, num_bots * 2, quads).unwrap(),
+            hit_points_data: hit_points.build_instanced(display, num_bots, quads).unwrap(),
+            shoot_power_data: shoot_power.build_instanced(display, num_bots, quads).unwrap(),
+            border_data: border_data,
+            debug_data: DynamicData::new(display,
+                                         MAX_DEBUG_VERTICES,
+                                         PrimitiveType::LinesList,
+                                         include_str!("bin/debug_vshader.glsl"),
+                                         include_str!("bin/fshader.glsl"))
+                .unwrap(),
+            trail_data: DynamicData::new(display,
+                                         MAX_TRAIL_VERTICES,
+                                         PrimitiveType::LinesList,
+                                         include_str!("bin/debug_vshader.glsl"),
+                                         include_str!("bin/fshader.glsl"))
+                .unwrap(),
+            effect_data: DynamicData::new(display,
+                                          MAX_EFFECT_VERTICES,
+                                          PrimitiveType::LinesList,
+                                          include_str!("bin/debug_vshader.glsl"),
+                                          include_str!("bin/fshader.glsl"))
+                .unwrap(),
+            sweep_data: DynamicData::new(display,
+                                         MAX_SWEEP_VERTICES,
+                                         PrimitiveType::TrianglesList,
+                                         include_str!("bin/debug_vshader.glsl"),
+                                         include_str!("bin/fshader.glsl"))
+                .unwrap(),
+            sweep_edge_data: DynamicData::new(display,
+                                              num_bots * 2,
+                                              PrimitiveType::LinesList,
+                                              include_str!("bin/debug_vshader.glsl"),
+                                              include_str!("bin/fshader.glsl"))
+                .unwrap(),
+            // Room for a label per robot, plus the scoreboard and status line.
+            text: TextRenderer::new(display, num_bots * 32 + 4096).unwrap(),
+
+            global_matrix: Matrix4::identity().into(),
+            debug_hidden: HashSet::new(),
 
-            bots: bots,
+            body_matrices: Vec::new(),
+            selected: None,
+            histories: vec![VecDeque::new(); num_bots],
+            trails: vec![VecDeque::new(); num_bots],
+            effects: Vec::new(),
+            prev_radar_headings: Vec::new(),
+            show_sweeps: true,
+
+            labels: bots.iter().map(|bot| format!("{} {}", bot.id, bot.name())).collect(),
+            bots: bots.to_vec(),
+            base_colors: bots.iter().enumerate().map(|(i, bot)| base_color(i, bot)).collect(),
+            show_labels: true,
+            show_scoreboard: true,
+            sort_key: SortKey::HitPoints,
+
+            frames: 0,
+            rate_start: Instant::now(),
+            rate_start_tick: 0,
+            fps: 0.0,
+            sim_speed: 0.0,
+
+            camera: camera,
+            config: config.clone(),
+
+            snapshots: snapshots,
+            current: Snapshot::default(),
         }
     }
 
+    /// Handle a window event. L toggles robot labels, R toggles radar sweeps,
+    /// H toggles the scoreboard and S changes what it is sorted by. D toggles
+    /// the debug drawings of the robot being followed, or of every robot if
+    /// none is. V saves the current tick as an SVG image. Clicking on a robot
+    /// selects it for the inspect panel, and Escape clears the selection.
+    /// Everything else is passed to the camera.
+    pub fn handle_event(&mut self, event: &Event) {
+        let num_bots = self.current.bots.len();
+
+        match *event {
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::L)) => {
+                self.show_labels = !self.show_labels
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::R)) => {
+                self.show_sweeps = !self.show_sweeps
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::H)) => {
+                self.show_scoreboard = !self.show_scoreboard
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::S)) => {
+                self.sort_key = self.sort_key.next()
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::V)) => {
+                self.save_svg()
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::D)) => {
+                match self.camera.following() {
+                    Some(bot) => {
+                        if !self.debug_hidden.remove(&bot) {
+                            self.debug_hidden.insert(bot);
+                        }
+                    }
+
+                    // Show everything if anything is hidden, otherwise hide
+                    // everything.
+                    None if self.debug_hidden.is_empty() => self.debug_hidden.extend(0..num_bots),
+                    None => self.debug_hidden.clear(),
+                }
+            }
+
+            Event::MouseInput(ElementState::Pressed, MouseButton::Left) => {
+                let (x, y) = self.camera.cursor();
+                let pos = self.camera.window_to_world(x, y);
+
+                if let Some(bot) = self.pick(pos) {
+                    self.selected = Some(bot);
+                }
+
+                self.camera.handle_event(event, num_bots);
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::Escape)) => {
+                self.selected = None;
+                self.camera.handle_event(event, num_bots);
+            }
+
+            _ => self.camera.handle_event(event, num_bots),
+        }
+    }
+
+    /// The robot whose body is at the given world position, if any. Robots
+    /// drawn on top are picked first.
+    fn pick(&self, pos: Vector2) -> Option<usize> {
+        let point = Vector4::new(pos.x as f32, pos.y as f32, 0.0, 1.0);
+
+        self.body_matrices.iter().rposition(|matrix| {
+            matrix.invert().map_or(false, |inverse| {
+                let local = inverse * point;
+                local.x.abs() <= BODY_HALF_LENGTH && local.y.abs() <= BODY_HALF_WIDTH
+            })
+        })
+    }
+
+    /// Keep the recent history of each robot from a snapshot's events.
+    fn record_history(&mut self, snapshot: &Snapshot) {
+        for event in &snapshot.events {
+            let (bot, entries): (usize, Vec<String>) = match *event {
+                WorldEvent::Responded { bot, ref responses } => {
+                    (bot, responses.iter().map(|resp| format!("{:?}", resp)).collect())
+                }
+                WorldEvent::Strike { bot, ref error } => (bot, vec![format!("Strike: {}", error)]),
+                _ => continue,
+            };
+
+            if let Some(history) = self.histories.get_mut(bot) {
+                for entry in entries {
+                    history.push_back(format!("{} {}", snapshot.tick, entry));
+
+                    if history.len() > HISTORY_LENGTH {
+                        history.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    /// The world position under the mouse cursor.
+    pub fn cursor_world_pos(&self) -> Vector2 {
+        let (x, y) = self.camera.cursor();
+        self.camera.window_to_world(x, y)
+    }
+
     /// Update GPU memory to synchronise with the current state of the world and
     /// robots.
     pub fn update(&mut self) {
-        let bots = {
-            let bots = self.bots.lock().unwrap();
-            bots.clone()
-        };
+        while let Ok(snapshot) = self.snapshots.try_recv() {
+            // Going back in a replay would leave history and effects from the
+            // future.
+            if snapshot.tick < self.current.tick {
+                for history in &mut self.histories {
+                    history.clear();
+                }
+
+                self.effects.clear();
+            }
+
+            // Without the previous tick, there is no sweep to show.
+            self.prev_radar_headings = if snapshot.tick == self.current.tick + 1 {
+                self.current.bots.iter().map(|bot| bot.radar_heading).collect()
+            } else {
+                snapshot.bots.iter().map(|bot| bot.radar_heading).collect()
+            };
+
+            self.record_history(&snapshot);
+            self.record_effects(&snapshot);
+            self.current = snapshot;
+        }
+
+        // The snapshot is taken out while the buffers are updated, rather than
+        // cloned every frame.
+        let snapshot = mem::replace(&mut self.current, Snapshot::default());
+        self.update_buffers(&snapshot);
+        self.current = snapshot;
+    }
+
+    /// Update every buffer, and queue this frame's text, for the given
+    /// snapshot.
+    fn update_buffers(&mut self, snapshot: &Snapshot) {
+        let bots = &snapshot.bots;
 
-        fn update_one<F>(data: &mut Data, bots: &[BotState], select_heading_pos: F)
+        fn update_one<F>(data: &mut Data,
+                         bots: &[BotState],
+                         colors: &[[f32; 4]],
+                         select_heading_pos: F)
             where F: Fn(&BotState) -> (f64, Vector2)
         {
 
-            let iter = bots.iter().map(|bot| {
+            let iter = bots.iter().zip(colors).map(|(bot, &color)| {
                 let (heading, pos) = select_heading_pos(bot);
-
-                let rot = mat_3_to_4(Matrix3::from(Quaternion::from_angle_z(rad(heading as f32))));
-                let transl =
-                    Matrix4::from_translation(Vector3::new(pos.x as f32, pos.y as f32, 0.0));
-
-                Attr { instance_matrix: (transl * rot).into() }
+                Attr::new(bot_matrix(heading, pos), color)
             });
 
             data.update_instances(iter).unwrap();
         }
 
-        update_one(&mut self.body_data, &bots, |bot| (bot.heading, bot.pos));
+        let colors: Vec<_> = bots.iter()
+            .enumerate()
+            .map(|(i, bot)| self.bot_color(i, bot))
+            .collect();
+        let light_colors: Vec<_> = colors.iter().map(|&color| lighten(color)).collect();
+
+        self.global_matrix = self.camera.matrix(bots).into();
+        let uniforms = GlobalUniforms { global_matrix: self.global_matrix };
+
+        for data in self.all_data_mut() {
+            data.uniforms = uniforms.clone().into_uniforms();
+        }
+
+        self.body_matrices = bots.iter().map(|bot| bot_matrix(bot.heading, bot.pos)).collect();
+
+        update_one(&mut self.body_data, bots, &colors, |bot| (bot.heading, bot.pos));
         update_one(&mut self.radar_data,
-                   &bots,
+                   bots,
+                   &light_colors,
                    |bot| (bot.radar_heading, bot.pos));
-        update_one(&mut self.gun_data, &bots, |bot| (bot.gun_heading, bot.pos));
+        update_one(&mut self.gun_data, bots, &colors, |bot| (bot.gun_heading, bot.pos));
+
+        let max_bullets = self.bullet_data.max_instances();
+        let bullets = snapshot.bullets.iter().take(max_bullets).map(|bullet| {
+            let size = bullet_size(bullet.power);
+            let transl = Matrix4::from_translation(Vector3::new(bullet.pos.x as f32,
+                                                                bullet.pos.y as f32,
+                                                                0.0));
+
+            let color = light_colors.get(bullet.owner).cloned().unwrap_or(WHITE);
+
+            Attr::new(transl * Matrix4::from_scale(size), color)
+        });
+
+        self.bullet_data.update_instances(bullets).unwrap();
+
+        let config = &self.config;
+
+        let bar_backs: Vec<_> = bots.iter()
+            .flat_map(|bot| vec![bar_attr(bot.pos, 0, 1.0), bar_attr(bot.pos, 1, 1.0)])
+            .collect();
+        let hit_points = bots.iter()
+            .map(|bot| bar_attr(bot.pos, 0, bot.hit_points / config.max_hit_points));
+        let shoot_power = bots.iter()
+            .map(|bot| bar_attr(bot.pos, 1, bot.shoot_power / config.max_shoot_power));
+
+        self.bar_back_data.update_instances(bar_backs).unwrap();
+        self.hit_points_data.update_instances(hit_points).unwrap();
+        self.shoot_power_data.update_instances(shoot_power).unwrap();
+
+        self.update_lines(snapshot);
+        self.update_effects(snapshot, &colors);
+        self.update_sweeps(snapshot, &colors);
+        self.measure_rates(snapshot.tick);
+
+        if self.show_labels {
+            self.queue_labels(bots);
+        }
+
+        if self.show_scoreboard {
+            self.queue_scoreboard(snapshot);
+        }
+
+        self.queue_status(snapshot.tick);
+
+        if let Some(bot) = self.selected {
+            self.queue_inspect_panel(snapshot, bot);
+        }
+    }
+
+    /// The colour to draw the robot with the given index in. Robots which are
+    /// out of the match are greyed out.
+    fn bot_color(&self, index: usize, bot: &BotState) -> [f32; 4] {
+        let base = self.base_colors.get(index).cloned().unwrap_or_else(|| palette_color(index));
+        bot_color(bot, base)
+    }
+
+    /// Build the lines for each shown debug drawing, queueing any text, and
+    /// for highlighting the selected robot.
+    fn update_lines(&mut self, snapshot: &Snapshot) {
+        let mut vertices = Vec::new();
+        let hidden = &self.debug_hidden;
+
+        for drawing in snapshot.drawings.iter().filter(|drawing| !hidden.contains(&drawing.bot)) {
+            let color = [drawing.color[0], drawing.color[1], drawing.color[2], 1.0];
+
+            match drawing.shape {
+                DebugShape::Line { from, to } => push_line(&mut vertices, from, to, color),
+
+                DebugShape::Circle { centre, radius } => {
+                    push_arc(&mut vertices, centre, radius, 0.0, 2.0 * PI, color)
+                }
+
+                DebugShape::Point { pos } => {
+                    let (dx, dy) = (Vector2::new(POINT_SIZE, 0.0), Vector2::new(0.0, POINT_SIZE));
+                    push_line(&mut vertices, pos - dx, pos + dx, color);
+                    push_line(&mut vertices, pos - dy, pos + dy, color);
+                }
+
+                DebugShape::Text { pos, ref text } => {
+                    let pos = self.camera.world_to_window(pos);
+                    self.text.queue_text(text, pos, LABEL_SCALE, color);
+                }
+            }
+        }
+
+        // Highlight the selected robot, its radar arc and its bullets.
+        let selected = self.selected.and_then(|i| snapshot.bots.get(i).map(|bot| (i, bot)));
+
+        if let Some((i, bot)) = selected {
+            let radius = self.config.robot_radius * 1.5;
+            push_arc(&mut vertices, bot.pos, radius, 0.0, 2.0 * PI, HIGHLIGHT_COLOR);
+
+            let range = self.config.radar_range;
+            let (start, end) = self.radar_sweep(i, bot);
+            let edge = |angle: f64| bot.pos + Vector2::new(angle.cos(), angle.sin()) * range;
+
+            push_line(&mut vertices, bot.pos, edge(start), HIGHLIGHT_COLOR);
+            push_line(&mut vertices, bot.pos, edge(end), HIGHLIGHT_COLOR);
+            push_arc(&mut vertices, bot.pos, range, start, end, HIGHLIGHT_COLOR);
+        }
+
+        if let Some(selected) = self.selected {
+            for bullet in snapshot.bullets.iter().filter(|bullet| bullet.owner == selected) {
+                push_arc(&mut vertices, bullet.pos, 0.3, 0.0, 2.0 * PI, HIGHLIGHT_COLOR);
+            }
+        }
+
+        // Vertices come in pairs, so this never splits a line.
+        vertices.truncate(MAX_DEBUG_VERTICES);
+        self.debug_data.update_vertices(vertices).unwrap();
+    }
+
+    /// Extend each robot's trail, and start effects for the snapshot's events.
+    fn record_effects(&mut self, snapshot: &Snapshot) {
+        // Trails would jump across any ticks which were skipped.
+        let skipped = snapshot.tick != self.current.tick + 1;
+
+        for (trail, bot) in self.trails.iter_mut().zip(&snapshot.bots) {
+            if skipped {
+                trail.clear();
+            }
+
+            // Dead robots' trails shrink away.
+            if bot.hit_points > 0.0 {
+                trail.push_back(bot.pos);
+            }
+
+            if trail.len() > TRAIL_LENGTH || bot.hit_points <= 0.0 {
+                trail.pop_front();
+            }
+        }
+
+        for (i, event) in snapshot.events.iter().enumerate() {
+            let seed = snapshot.tick.wrapping_mul(1_000_003).wrapping_add(i as u64);
+
+            let (kind, bot, heading) = match *event {
+                WorldEvent::Shot { bot, .. } => {
+                    let heading = snapshot.bots.get(bot).map_or(0.0, |bot| bot.gun_heading);
+                    (EffectKind::MuzzleFlash, bot, heading)
+                }
+                WorldEvent::Hit { bot, .. } => (EffectKind::Sparks, bot, 0.0),
+                WorldEvent::Death { bot } => (EffectKind::Explosion, bot, 0.0),
+                WorldEvent::Scan { target, .. } => (EffectKind::ScanFlash, target, 0.0),
+                _ => continue,
+            };
+
+            let bot_state = match snapshot.bots.get(bot) {
+                Some(bot_state) => bot_state,
+                None => continue,
+            };
+
+            let pos = match kind {
+                EffectKind::MuzzleFlash => {
+                    bot_state.pos + Vector2::new(heading.cos(), heading.sin()) * GUN_LENGTH
+                }
+                _ => bot_state.pos,
+            };
+
+            let color = match kind {
+                EffectKind::MuzzleFlash => [1.0, 1.0, 0.7, 1.0],
+                EffectKind::Sparks => [1.0, 0.7, 0.2, 1.0],
+                EffectKind::Explosion => [1.0, 0.5, 0.1, 1.0],
+                EffectKind::ScanFlash => [0.6, 1.0, 1.0, 1.0],
+            };
+
+            self.effects.push(Effect {
+                kind: kind,
+                pos: pos,
+                heading: heading,
+                color: color,
+                start_tick: snapshot.tick,
+                seed: seed,
+            });
+        }
+    }
+
+    /// Build the lines for trails and effects as they are at the snapshot's
+    /// tick, dropping effects which have finished.
+    fn update_effects(&mut self, snapshot: &Snapshot, colors: &[[f32; 4]]) {
+        let tick_seconds = self.config.tick_seconds();
+
+        let mut trail_vertices = Vec::new();
+
+        for (trail, &color) in self.trails.iter().zip(colors) {
+            // Fade towards the background from the newest point to the oldest.
+            let len = trail.len() as f32;
+
+            for (i, (&from, &to)) in trail.iter().zip(trail.iter().skip(1)).enumerate() {
+                push_line(&mut trail_vertices, from, to, fade(color, (i + 1) as f32 / len * 0.5));
+            }
+        }
+
+        self.effects.retain(|effect| {
+            let age = snapshot.tick.saturating_sub(effect.start_tick) as f64 * tick_seconds;
+            age < effect.kind.seconds()
+        });
+
+        let mut effect_vertices = Vec::new();
+
+        for effect in &self.effects {
+            let age = snapshot.tick.saturating_sub(effect.start_tick) as f64 * tick_seconds;
+            let t = age / effect.kind.seconds();
+            let color = fade(effect.color, (1.0 - t) as f32);
+            let dir = |angle: f64| Vector2::new(angle.cos(), angle.sin());
+
+            match effect.kind {
+                EffectKind::MuzzleFlash => {
+                    for i in 0..5 {
+                        let angle = effect.heading + (i as f64 - 2.0) * 0.3;
+                        let length = (0.2 + 0.3 * noise(effect.seed, i)) * (1.0 - t);
+                        push_line(&mut effect_vertices,
+                                  effect.pos,
+                                  effect.pos + dir(angle) * length,
+                                  color);
+                    }
+                }
+
+                EffectKind::Sparks => {
+                    for i in 0..8 {
+                        let angle = noise(effect.seed, i) * 2.0 * PI;
+                        let speed = 2.0 + 4.0 * noise(effect.seed, i + 8);
+                        let from = effect.pos + dir(angle) * (speed * age);
+                        push_line(&mut effect_vertices,
+                                  from,
+                                  from + dir(angle) * (speed * 0.04),
+                                  color);
+                    }
+                }
+
+                EffectKind::Explosion => {
+                    let radius = 0.5 + 2.5 * t;
+                    push_arc(&mut effect_vertices, effect.pos, radius, 0.0, 2.0 * PI, color);
+
+                    for i in 0..12 {
+                        let angle = noise(effect.seed, i) * 2.0 * PI;
+                        let distance = radius * (0.3 + 0.7 * noise(effect.seed, i + 12));
+                        let from = effect.pos + dir(angle) * distance;
+                        push_line(&mut effect_vertices, from, from + dir(angle) * 0.3, color);
+                    }
+                }
+
+                EffectKind::ScanFlash => {
+                    let radius = self.config.robot_radius * (1.2 + 0.5 * t);
+                    push_arc(&mut effect_vertices, effect.pos, radius, 0.0, 2.0 * PI, color);
+                }
+            }
+        }
+
+        // Vertices come in pairs, so this never splits a line.
+        trail_vertices.truncate(MAX_TRAIL_VERTICES);
+        effect_vertices.truncate(MAX_EFFECT_VERTICES);
+
+        self.trail_data.update_vertices(trail_vertices).unwrap();
+        self.effect_data.update_vertices(effect_vertices).unwrap();
+    }
+
+    /// Build the translucent sector swept by each living robot's radar since the
+    /// last tick, out to the radar's range.
+    fn update_sweeps(&mut self, snapshot: &Snapshot, colors: &[[f32; 4]]) {
+        let mut vertices = Vec::new();
+        let mut edge_vertices = Vec::new();
+
+        if self.show_sweeps {
+            let range = self.config.radar_range;
+
+            for (i, (bot, &color)) in snapshot.bots.iter().zip(colors).enumerate() {
+                if bot.hit_points <= 0.0 {
+                    continue;
+                }
+
+                let (start, end) = self.radar_sweep(i, bot);
+                let point = |angle: f64| bot.pos + Vector2::new(angle.cos(), angle.sin()) * range;
+                let fill = [color[0], color[1], color[2], SWEEP_ALPHA];
+                let edge = [color[0], color[1], color[2], SWEEP_EDGE_ALPHA];
+
+                let segments = (end - start) / (2.0 * PI) * CIRCLE_SEGMENTS as f64;
+                let segments = segments.ceil() as usize;
+                for s in 0..segments {
+                    let a0 = start + (end - start) * s as f64 / segments as f64;
+                    let a1 = start + (end - start) * (s + 1) as f64 / segments as f64;
+
+                    vertices.push(LineVertex::new(bot.pos, fill));
+                    vertices.push(LineVertex::new(point(a0), fill));
+                    vertices.push(LineVertex::new(point(a1), fill));
+                }
+
+                push_line(&mut edge_vertices, bot.pos, point(bot.radar_heading), edge);
+            }
+        }
+
+        // Vertices come in threes, and the limit is a multiple of three, so
+        // this never splits a triangle.
+        vertices.truncate(MAX_SWEEP_VERTICES / 3 * 3);
+
+        self.sweep_data.update_vertices(vertices).unwrap();
+        self.sweep_edge_data.update_vertices(edge_vertices).unwrap();
+    }
+
+    /// Save the current tick, with trails and radar sweeps, as an SVG image
+    /// named after the tick in the working directory.
+    fn save_svg(&self) {
+        let path = format!("tick-{:06}.svg", self.current.tick);
+
+        let extras = svg::Extras {
+            trails: self.trails.iter().map(|trail| trail.iter().cloned().collect()).collect(),
+            prev_radar_headings: self.prev_radar_headings.clone(),
+        };
+
+        let result = File::create(&path).and_then(|file| {
+            svg::write_svg(BufWriter::new(file), &self.config, &self.bots, &self.current, &extras)
+        });
+
+        match result {
+            Ok(()) => println!("Saved {}", path),
+            Err(err) => println!("Error while saving {}: {}", path, err),
+        }
+    }
+
+    /// The start and end of the arc swept by a robot's radar since the last
+    /// tick, in radians going anticlockwise.
+    fn radar_sweep(&self, index: usize, bot: &BotState) -> (f64, f64) {
+        let from = self.prev_radar_headings.get(index).cloned().unwrap_or(bot.radar_heading);
+
+        if from <= bot.radar_heading {
+            (from, bot.radar_heading)
+        } else {
+            (bot.radar_heading, from)
+        }
+    }
+
+    /// Count a frame, and recalculate the frame rate and simulation speed if
+    /// enough time has passed.
+    fn measure_rates(&mut self, tick: u64) {
+        self.frames += 1;
+
+        let secs = duration_float(self.rate_start.elapsed());
+
+        if secs >= 1.0 {
+            // Seeking backwards in a replay counts as no progress.
+            let ticks = tick.saturating_sub(self.rate_start_tick) as f64;
+
+            self.fps = self.frames as f64 / secs;
+            self.sim_speed = ticks / secs / self.config.ticks_per_second as f64;
+
+            self.frames = 0;
+            self.rate_start = Instant::now();
+            self.rate_start_tick = tick;
+        }
+    }
+
+    /// Queue each robot's label, centred above its bars.
+    fn queue_labels(&mut self, bots: &[BotState]) {
+        let height = GLYPH_HEIGHT as f32 * LABEL_SCALE;
+
+        for (bot, label) in bots.iter().zip(&self.labels) {
+            let above = bot.pos + Vector2::new(0.0, (BAR_OFFSET + BAR_HEIGHT * 3.0) as f64);
+            let (x, y) = self.camera.world_to_window(above);
+            let width = TextRenderer::text_width(label, LABEL_SCALE);
+
+            let color = if bot.hit_points > 0.0 {
+                TEXT_COLOR
+            } else {
+                DEAD_TEXT_COLOR
+            };
+
+            self.text.queue_text(label, (x - width * 0.5, y - height), LABEL_SCALE, color);
+        }
+    }
+
+    /// Queue the scoreboard in the top left of the window, listing the best
+    /// robots by the current sort key.
+    fn queue_scoreboard(&mut self, snapshot: &Snapshot) {
+        let bots = &snapshot.bots;
+        let sort_key = self.sort_key;
+        let score = |i: usize| snapshot.scores.get(i).cloned().unwrap_or_else(Score::default);
+
+        // Sorting is stable, so ties stay in world order.
+        let mut order: Vec<usize> = (0..bots.len()).collect();
+        order.sort_by(|&a, &b| {
+            let a = sort_key.value(&bots[a], &score(a));
+            let b = sort_key.value(&bots[b], &score(b));
+            b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+        });
+
+        let mut lines = vec![(format!("Sorted by {}", sort_key.name()), TEXT_COLOR),
+                             (format!("{:<16} {:>5} {:>5} {:>7}", "Robot", "HP", "Kills", "Damage"),
+                              TEXT_COLOR)];
+
+        for &i in order.iter().take(SCOREBOARD_ROWS) {
+            let bot = &bots[i];
+            let bot_score = score(i);
+            let name: String = self.labels
+                .get(i)
+                .map_or_else(|| i.to_string(), |label| label.chars().take(16).collect());
+
+            let color = if self.camera.following() == Some(i) {
+                FOLLOWED_TEXT_COLOR
+            } else if bot.hit_points > 0.0 {
+                TEXT_COLOR
+            } else {
+                DEAD_TEXT_COLOR
+            };
+
+            lines.push((format!("{:<16} {:>5.0} {:>5} {:>7.1}",
+                                name,
+                                bot.hit_points,
+                                bot_score.kills,
+                                bot_score.damage_dealt),
+                        color));
+        }
+
+        let line_height = LINE_HEIGHT as f32 * HUD_SCALE;
+        let width = lines.iter()
+            .map(|&(ref line, _)| TextRenderer::text_width(line, HUD_SCALE))
+            .fold(0.0, f32::max);
+        let height = lines.len() as f32 * line_height;
+
+        self.text.queue_rect((HUD_MARGIN * 0.5, HUD_MARGIN * 0.5),
+                             (width + HUD_MARGIN, height + HUD_MARGIN),
+                             HUD_BACK_COLOR);
+
+        for (row, &(ref line, color)) in lines.iter().enumerate() {
+            self.text.queue_text(line,
+                                 (HUD_MARGIN, HUD_MARGIN + row as f32 * line_height),
+                                 HUD_SCALE,
+                                 color);
+        }
+    }
+
+    /// Queue the inspect panel for the given robot in the top right of the
+    /// window.
+    fn queue_inspect_panel(&mut self, snapshot: &Snapshot, index: usize) {
+        let bot = match snapshot.bots.get(index) {
+            Some(bot) => bot,
+            None => return,
+        };
+
+        let diagnostics = snapshot.diagnostics
+            .get(index)
+            .cloned()
+            .unwrap_or_else(Diagnostics::default);
+
+        let color = match bot.color {
+            Some(c) => format!("{:.2}, {:.2}, {:.2}", c[0], c[1], c[2]),
+            None => "auto".to_owned(),
+        };
+
+        let mut lines = vec![
+            self.labels.get(index).cloned().unwrap_or_else(|| index.to_string()),
+            format!("Position    {:.2}, {:.2}", bot.pos.x, bot.pos.y),
+            format!("Heading     {:.3}", bot.heading),
+            format!("Gun         {:.3}", bot.gun_heading),
+            format!("Radar       {:.3}", bot.radar_heading),
+            format!("Speed       {:.3}", bot.speed),
+            format!("Thrust      {:.3}", bot.thrust),
+            format!("Turn rate   {:.3}", bot.turn_rate),
+            format!("Gun turn    {:.3}", bot.gun_turn_rate),
+            format!("Radar turn  {:.3}", bot.radar_turn_rate),
+            format!("Hit points  {:.1}", bot.hit_points),
+            format!("Shoot power {:.1}", bot.shoot_power),
+            format!("Colour      {}", color),
+            String::new(),
+            format!("Latency     {:.1} ms", diagnostics.latency * 1000.0),
+            format!("Errors      {}", diagnostics.errors),
+            format!("Strikes     {}/{}", diagnostics.strikes, self.config.max_strikes),
+            format!("Dropped     {} drawings", diagnostics.dropped_drawings),
+        ];
+
+        if let Some(ref error) = diagnostics.last_error {
+            lines.push(format!("Last error  {}", error));
+        }
+
+        if diagnostics.disqualified {
+            lines.push("Disqualified".to_owned());
+        }
+
+        lines.push(String::new());
+        lines.push("Recent responses".to_owned());
+
+        if let Some(history) = self.histories.get(index) {
+            lines.extend(history.iter().cloned());
+        }
+
+        let lines: Vec<String> = lines.into_iter()
+            .map(|line| line.chars().take(PANEL_LINE_LENGTH).collect())
+            .collect();
+        let text = lines.join("\n");
+
+        let (window_width, _) = self.camera.window_size();
+        let width = TextRenderer::text_width(&text, HUD_SCALE);
+        let height = lines.len() as f32 * LINE_HEIGHT as f32 * HUD_SCALE;
+        let x = window_width as f32 - width - HUD_MARGIN;
+
+        self.text.queue_rect((x - HUD_MARGIN * 0.5, HUD_MARGIN * 0.5),
+                             (width + HUD_MARGIN, height + HUD_MARGIN),
+                             HUD_BACK_COLOR);
+        self.text.queue_text(&text, (x, HUD_MARGIN), HUD_SCALE, TEXT_COLOR);
+    }
+
+    /// Queue the tick count, frame rate and simulation speed in the bottom left
+    /// of the window.
+    fn queue_status(&mut self, tick: u64) {
+        let status = format!("Tick {}  {:.0} FPS  {:.2}x", tick, self.fps, self.sim_speed);
+        let (_, window_height) = self.camera.window_size();
+        let y = window_height as f32 - HUD_MARGIN - GLYPH_HEIGHT as f32 * HUD_SCALE;
+
+        self.text.queue_text(&status, (HUD_MARGIN, y), HUD_SCALE, TEXT_COLOR);
+    }
+
+    fn all_data_mut(&mut self) -> Vec<&mut Data> {
+        vec![&mut self.body_data,
+             &mut self.radar_data,
+             &mut self.gun_data,
+             &mut self.bullet_data,
+             &mut self.bar_back_data,
+             &mut self.hit_points_data,
+             &mut self.shoot_power_data,
+             &mut self.border_data]
     }
 
     /// Issue the OpenGL draw calls to draw the world for this frame. Should
-    /// usually call `update` first.
-    pub fn draw<S>(&self, surface: &mut S, params: &DrawParameters) -> Result<(), DrawError>
+    /// usually call `update` first, which also queues up this frame's text.
+    pub fn draw<S>(&mut self, surface: &mut S, params: &DrawParameters) -> Result<(), DrawError>
         where S: Surface
     {
 
+        let uniforms = uniform! { global_matrix: self.global_matrix };
+
+        try!(self.border_data.draw(surface, params));
+        try!(self.sweep_data.draw(surface, &uniforms, params));
+        try!(self.sweep_edge_data.draw(surface, &uniforms, params));
+        try!(self.trail_data.draw(surface, &uniforms, params));
         try!(self.body_data.draw(surface, params));
         try!(self.gun_data.draw(surface, params));
         try!(self.radar_data.draw(surface, params));
+        try!(self.bullet_data.draw(surface, params));
+        try!(self.effect_data.draw(surface, &uniforms, params));
+        try!(self.bar_back_data.draw(surface, params));
+        try!(self.hit_points_data.draw(surface, params));
+        try!(self.shoot_power_data.draw(surface, params));
+
+        try!(self.debug_data.draw(surface, &uniforms, params));
+
+        try!(self.text.draw(surface, params));
 
         Ok(())
     }
 }
 
+/// Shows a match in an OpenGL window.
+pub struct GlViewer {
+    display: GlutinFacade,
+    draw_state: DrawState,
+
+    /// Digits typed so far, to jump to that tick when Return is pressed.
+    typed_tick: String,
+
+    /// The keyboard of a robot controlled by the person at the window, if
+    /// there is one.
+    keyboard: Option<Keyboard>,
+}
+
+impl GlViewer {
+    /// Open a window to show the given snapshots in, as for `DrawState::new`.
+    pub fn new(snapshots: Receiver<Snapshot>, config: &Config, bots: &[BotInfo]) -> Self {
+        use glium::DisplayBuild;
+
+        let display = glutin::WindowBuilder::new()
+            .with_vsync()
+            .build_glium()
+            .unwrap();
+
+        let draw_state = DrawState::new(&display, snapshots, config, bots);
+
+        GlViewer {
+            display: display,
+            draw_state: draw_state,
+            typed_tick: String::new(),
+            keyboard: None,
+        }
+    }
+
+    /// Forward window events to a keyboard-controlled robot. Any event it uses
+    /// isn't handled by the viewer.
+    pub fn with_keyboard(mut self, keyboard: Keyboard) -> Self {
+        self.keyboard = Some(keyboard);
+        self
+    }
+
+    /// The control input for a window event, if any. Digits are collected
+    /// until Return is pressed, then jumped to.
+    fn input(&mut self, event: &Event) -> Option<Input> {
+        let key = match *event {
+            Event::ReceivedCharacter(c) if c.is_digit(10) => {
+                self.typed_tick.push(c);
+                return None;
+            }
+            Event::KeyboardInput(ElementState::Pressed, _, Some(key)) => key,
+            _ => return None,
+        };
+
+        match key {
+            VirtualKeyCode::Space => Some(Input::TogglePause),
+            VirtualKeyCode::Period |
+            VirtualKeyCode::Right => Some(Input::StepForward),
+            VirtualKeyCode::Comma |
+            VirtualKeyCode::Left => Some(Input::StepBack),
+            VirtualKeyCode::Equals |
+            VirtualKeyCode::Add => Some(Input::Faster),
+            VirtualKeyCode::Minus |
+            VirtualKeyCode::Subtract => Some(Input::Slower),
+            VirtualKeyCode::RBracket => Some(Input::NextEvent),
+            VirtualKeyCode::LBracket => Some(Input::PrevEvent),
+            VirtualKeyCode::Home => Some(Input::Seek(0)),
+            VirtualKeyCode::End => Some(Input::Seek(u64::max_value())),
+            VirtualKeyCode::F5 => Some(Input::Reload),
+            VirtualKeyCode::Return => {
+                let tick = self.typed_tick.parse().ok();
+                self.typed_tick.clear();
+                tick.map(Input::Seek)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Viewer for GlViewer {
+    /// Draw the robots until the window is closed. Every other window event
+    /// is passed to the drawing state, then turned into control input.
+    fn run(&mut self, handle_input: &mut FnMut(Input)) {
+        loop {
+            self.draw_state.update();
+
+            let params = DrawParameters {
+                multisampling: true,
+                smooth: Some(::glium::Smooth::Nicest),
+                blend: ::glium::Blend::alpha_blending(),
+                ..Default::default()
+            };
+
+            let mut target = self.display.draw();
+            target.clear_color(0.0, 0.0, 0.0, 0.0);
+            self.draw_state.draw(&mut target, &params).unwrap();
+            target.finish().unwrap();
+
+            let events: Vec<_> = self.display.poll_events().collect();
+
+            for event in events {
+                if let Event::Closed = event {
+                    return;
+                }
+
+                if let Some(ref keyboard) = self.keyboard {
+                    if keyboard.handle_event(&event, self.draw_state.cursor_world_pos()) {
+                        continue;
+                    }
+                }
+
+                self.draw_state.handle_event(&event);
+
+                if let Some(input) = self.input(&event) {
+                    handle_input(input);
+                }
+            }
+        }
+    }
+}
+
+/// The instance attribute for a bar above a robot at the given position. Row 0
+/// is nearest the robot. The bar is filled to the given fraction.
+fn bar_attr(pos: Vector2, row: u32, fraction: f64) -> Attr {
+    let fraction = fraction.max(0.0).min(1.0) as f32;
+
+    let transl = Matrix4::from_translation(Vector3::new(pos.x as f32 - BAR_WIDTH * 0.5,
+                                                        pos.y as f32 + BAR_OFFSET +
+                                                        row as f32 * BAR_HEIGHT * 1.5,
+                                                        0.0));
+
+    Attr::new(transl * Matrix4::from_nonuniform_scale(fraction, 1.0, 1.0), WHITE)
+}
+
+/// The transform for a robot part with the given heading and position.
+fn bot_matrix(heading: f64, pos: Vector2) -> Matrix4<f32> {
+    let rot = mat_3_to_4(Matrix3::from(Quaternion::from_angle_z(rad(heading as f32))));
+    let transl = Matrix4::from_translation(Vector3::new(pos.x as f32, pos.y as f32, 0.0));
+
+    transl * rot
+}
+
+fn push_line(vertices: &mut Vec<LineVertex>, from: Vector2, to: Vector2, color: [f32; 4]) {
+    vertices.push(LineVertex::new(from, color));
+    vertices.push(LineVertex::new(to, color));
+}
+
+/// Add the lines for an arc of a circle, between angles in radians going
+/// anticlockwise. A whole circle uses `CIRCLE_SEGMENTS` lines.
+fn push_arc(vertices: &mut Vec<LineVertex>,
+            centre: Vector2,
+            radius: f64,
+            start: f64,
+            end: f64,
+            color: [f32; 4]) {
+    let segments = ((end - start).abs() / (2.0 * PI) * CIRCLE_SEGMENTS as f64).ceil().max(1.0);
+    let point = |i: f64| {
+        let angle = start + (end - start) * i / segments;
+        centre + Vector2::new(angle.cos(), angle.sin()) * radius
+    };
+
+    for i in 0..segments as usize {
+        push_line(vertices, point(i as f64), point(i as f64 + 1.0), color);
+    }
+}
+
+/// The colour of the robot with the given index when it hasn't chosen its own.
+/// Robots on a team share its colour.
+pub fn base_color(index: usize, bot: &BotInfo) -> [f32; 3] {
+    palette_color(bot.team.map_or(index, |team| team as usize))
+}
+
+/// The colour to draw a robot in, given its colour when it hasn't chosen its
+/// own. Robots which are out of the match are greyed out.
+pub fn bot_color(bot: &BotState, base: [f32; 3]) -> [f32; 4] {
+    if bot.hit_points <= 0.0 {
+        return DEAD_COLOR;
+    }
+
+    let rgb = bot.color.unwrap_or(base);
+    [rgb[0], rgb[1], rgb[2], 1.0]
+}
+
+/// The scale of the diamond drawn for a bullet with the given power.
+pub fn bullet_size(power: f64) -> f32 {
+    0.05 + 0.03 * power as f32
+}
+
+/// A colour for the given index which stands out from those of nearby indices.
+/// Hues are spread around the colour wheel by the golden ratio.
+fn palette_color(index: usize) -> [f32; 3] {
+    let hue = (index as f64 * 0.618033988749895) % 1.0;
+    hsv_to_rgb(hue as f32, 0.75, 0.95)
+}
+
+/// Convert a colour from hue, saturation and value, all between 0 and 1, to
+/// red, green and blue.
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let sector = (hue * 6.0).floor();
+    let f = hue * 6.0 - sector;
+
+    let p = value * (1.0 - saturation);
+    let q = value * (1.0 - saturation * f);
+    let t = value * (1.0 - saturation * (1.0 - f));
+
+    match sector as u32 % 6 {
+        0 => [value, t, p],
+        1 => [q, value, p],
+        2 => [p, value, t],
+        3 => [p, q, value],
+        4 => [t, p, value],
+        _ => [value, p, q],
+    }
+}
+
+/// Scale a colour towards black, which fades it into the background.
+fn fade(color: [f32; 4], brightness: f32) -> [f32; 4] {
+    [color[0] * brightness, color[1] * brightness, color[2] * brightness, color[3]]
+}
+
+/// A number between 0 and 1 which looks random, but is always the same for the
+/// same seed and index.
+fn noise(seed: u64, index: u64) -> f64 {
+    let mut x = seed ^ index.wrapping_mul(0x9e3779b97f4a7c15);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51afd7ed558ccd);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xc4ceb9fe1a85ec53);
+    x ^= x >> 33;
+
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Mix a colour halfway towards white.
+pub fn lighten(color: [f32; 4]) -> [f32; 4] {
+    [(color[0] + 1.0) * 0.5, (color[1] + 1.0) * 0.5, (color[2] + 1.0) * 0.5, color[3]]
+}
+
+impl Camera {
+    /// Create a camera showing the whole of a world of the given size.
+    pub fn new(world_size: Vector2, window_size: (u32, u32)) -> Self {
+        Camera {
+            world_size: world_size,
+            window_size: window_size,
+            centre: world_size * 0.5,
+            zoom: 1.0,
+            following: None,
+            cursor: (0, 0),
+            dragging: false,
+        }
+    }
+
+    /// The index of the robot the camera is locked onto, if any.
+    pub fn following(&self) -> Option<usize> {
+        self.following
+    }
+
+    /// Lock the camera onto the robot with the given index, or unlock it.
+    pub fn follow(&mut self, bot: Option<usize>) {
+        self.following = bot;
+    }
+
+    /// Update the camera in response to a window event. The number of robots
+    /// is needed to choose one to follow.
+    pub fn handle_event(&mut self, event: &Event, num_bots: usize) {
+        match *event {
+            Event::Resized(width, height) => self.window_size = (width, height),
+
+            Event::MouseInput(state, MouseButton::Left) => {
+                self.dragging = state == ElementState::Pressed
+            }
+
+            Event::MouseMoved(x, y) => {
+                if self.dragging {
+                    // Panning moves the camera off any robot it was following.
+                    let scale = 1.0 / self.pixels_per_unit();
+                    self.following = None;
+                    self.centre.x -= (x - self.cursor.0) as f64 * scale;
+                    self.centre.y += (y - self.cursor.1) as f64 * scale;
+                }
+
+                self.cursor = (x, y);
+            }
+
+            Event::MouseWheel(delta, _) => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y as f64,
+                    MouseScrollDelta::PixelDelta(_, y) => y as f64 / 20.0,
+                };
+
+                self.zoom = (self.zoom * 1.2f64.powf(lines)).max(0.1).min(100.0);
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::Tab)) => {
+                if num_bots > 0 {
+                    self.following = Some(self.following.map_or(0, |bot| (bot + 1) % num_bots));
+                }
+            }
+
+            Event::KeyboardInput(ElementState::Pressed, _, Some(VirtualKeyCode::Escape)) => {
+                self.following = None
+            }
+
+            _ => {}
+        }
+    }
+
+    /// The matrix which transforms world coordinates to the window's
+    /// coordinates, given the current state of the robots.
+    pub fn matrix(&mut self, bots: &[BotState]) -> Matrix4<f32> {
+        if let Some(bot) = self.following.and_then(|bot| bots.get(bot)) {
+            self.centre = bot.pos;
+        }
+
+        let scale = 1.0 / self.pixels_per_unit();
+        let half_width = self.window_size.0 as f64 * scale * 0.5;
+        let half_height = self.window_size.1 as f64 * scale * 0.5;
+
+        cgmath::ortho((self.centre.x - half_width) as f32,
+                      (self.centre.x + half_width) as f32,
+                      (self.centre.y - half_height) as f32,
+                      (self.centre.y + half_height) as f32,
+                      -1.0,
+                      1.0)
+    }
+
+    /// Convert a position in the window, in pixels from the top left, to world
+    /// coordinates.
+    pub fn window_to_world(&self, x: i32, y: i32) -> Vector2 {
+        let scale = 1.0 / self.pixels_per_unit();
+
+        Vector2::new(self.centre.x + (x as f64 - self.window_size.0 as f64 * 0.5) * scale,
+                     self.centre.y - (y as f64 - self.window_size.1 as f64 * 0.5) * scale)
+    }
+
+    /// Convert a world position to a position in the window, in pixels from
+    /// the top left. This uses the camera's position from the last call to
+    /// `matrix`.
+    pub fn world_to_window(&self, pos: Vector2) -> (f32, f32) {
+        let scale = self.pixels_per_unit();
+
+        ((self.window_size.0 as f64 * 0.5 + (pos.x - self.centre.x) * scale) as f32,
+         (self.window_size.1 as f64 * 0.5 - (pos.y - self.centre.y) * scale) as f32)
+    }
+
+    /// The last known cursor position, in pixels from the top left.
+    pub fn cursor(&self) -> (i32, i32) {
+        self.cursor
+    }
+
+    /// The size of the window, in pixels.
+    pub fn window_size(&self) -> (u32, u32) {
+        self.window_size
+    }
+
+    /// The number of pixels per world unit, at which the whole world just fits
+    /// into the window with its aspect ratio preserved, multiplied by the zoom.
+    fn pixels_per_unit(&self) -> f64 {
+        let fit_x = self.window_size.0 as f64 / self.world_size.x;
+        let fit_y = self.window_size.1 as f64 / self.world_size.y;
+
+        fit_x.min(fit_y).max(1e-6) * self.zoom
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 struct Vertex {
     position: [f32; 2],
@@ -158,10 +1605,37 @@ impl Vertex {
 #[derive(Copy, Clone)]
 struct Attr {
     instance_matrix: [[f32; 4]; 4],
+    instance_color: [f32; 4],
+}
+
+impl Attr {
+    fn new(matrix: Matrix4<f32>, color: [f32; 4]) -> Self {
+        Attr {
+            instance_matrix: matrix.into(),
+            instance_color: color,
+        }
+    }
+}
+
+/// A vertex of a line, in world coordinates.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct LineVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl LineVertex {
+    fn new(position: Vector2, color: [f32; 4]) -> Self {
+        LineVertex {
+            position: [position.x as f32, position.y as f32],
+            color: color,
+        }
+    }
 }
 
 implement_vertex!(Vertex, position, color);
-implement_vertex!(Attr, instance_matrix);
+implement_vertex!(LineVertex, position, color);
+implement_vertex!(Attr, instance_matrix, instance_color);
 
 #[derive(Clone)]
 struct GlobalUniforms {
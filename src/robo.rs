@@ -4,7 +4,6 @@ use ctl::RoboCtl;
 
 use std::sync::{Arc, Mutex, PoisonError, MutexGuard};
 use std::thread;
-use std::time::Instant;
 
 /// An asynchronous robot, whose behaviour is determined by the `Ctl` type.
 pub struct Robo<Ctl: RoboCtl> {
@@ -43,18 +42,14 @@ impl<Ctl: RoboCtl> Robo<Ctl> {
             try!(ctl.init().map_err(Error::Ctl));
         }
 
-        let mut prev_time = Instant::now();
-
         loop {
             // Wait until we are allowed to tick
-            if let Some(_tick_guard) = tick_lock.take() {
+            if let Some(tick_guard) = tick_lock.take() {
                 // Get a lock on the ctl
                 let mut ctl = try!(self.ctl.lock());
 
                 // Tell the `Ctl` to tick
-                let now = Instant::now();
-                try!(ctl.tick(now.duration_since(prev_time)).map_err(Error::Ctl));
-                prev_time = now;
+                try!(ctl.tick(tick_guard.elapsed()).map_err(Error::Ctl));
             } else {
                 // Get a lock on the ctl
                 let mut ctl = try!(self.ctl.lock());
@@ -74,6 +69,15 @@ impl<Ctl: RoboCtl> Robo<Ctl> {
         self.ctl.lock().map(|ctl| f(&*ctl)).map_err(Into::into)
     }
 
+    /// Do something with the underlying `Ctl` object, allowing it to be
+    /// modified. Should only be used while the robot isn't ticking.
+    pub fn with_ctl_mut<F, R>(&self, f: F) -> Result<R, Error<Ctl>>
+        where F: FnOnce(&mut Ctl) -> R
+    {
+
+        self.ctl.lock().map(|mut ctl| f(&mut *ctl)).map_err(Into::into)
+    }
+
     /// Asynchronously runs the robot in a new thread.
     ///
     /// ```
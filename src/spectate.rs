@@ -0,0 +1,135 @@
+//! Streaming matches to spectators' browsers. A small HTTP server serves a
+//! self-contained canvas viewer, which connects to a WebSocket server that
+//! sends it the match's header and then every snapshot as JSON.
+
+use replay::Header;
+use snapshot::Snapshot;
+
+use serde_json;
+use serde_json::error::Error as SerdeError;
+use ws;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+/// The viewer page. `WS_PORT` is replaced with the WebSocket server's port
+/// when it is served.
+const VIEWER_PAGE: &'static str = include_str!("spectate.html");
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    WebSocket(ws::Error),
+    Serialization(SerdeError),
+
+    /// The viewer page was to be served on the highest port, leaving none
+    /// above it for the WebSocket server.
+    NoWebSocketPort,
+}
+
+/// Serves a match to any number of spectators, who can join and leave at any
+/// time. Snapshots are serialised and sent on other threads, so spectators
+/// never hold up the simulation.
+pub struct Server {
+    http_addr: SocketAddr,
+}
+
+/// A spectator's connection, which is sent the header as soon as it opens.
+struct Spectator {
+    out: ws::Sender,
+    header: Arc<String>,
+}
+
+impl ws::Handler for Spectator {
+    fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+        self.out.send(self.header.as_str())
+    }
+}
+
+impl Server {
+    /// Start serving the viewer page at the given address, and streaming the
+    /// snapshots from the receiver (usually from `World::observe_lossy` or
+    /// `Player::observe`) over WebSocket on the next port up.
+    pub fn new(http_addr: SocketAddr,
+               header: &Header,
+               snapshots: Receiver<Snapshot>)
+               -> Result<Self, Error> {
+        let mut ws_addr = http_addr;
+        ws_addr.set_port(try!(http_addr.port().checked_add(1).ok_or(Error::NoWebSocketPort)));
+
+        let header = Arc::new(try!(serde_json::to_string(header).map_err(Error::Serialization)));
+
+        let socket = try!(ws::WebSocket::new(move |out| {
+                Spectator {
+                    out: out,
+                    header: header.clone(),
+                }
+            })
+            .map_err(Error::WebSocket));
+        let socket = try!(socket.bind(ws_addr).map_err(Error::WebSocket));
+        let broadcaster = socket.broadcaster();
+
+        thread::spawn(move || socket.run());
+
+        // Snapshots are sent to every open connection. A snapshot that can't
+        // be serialised or sent, say because the WebSocket server's queue is
+        // full, is skipped, and the next is tried as usual.
+        thread::spawn(move || {
+            for snapshot in snapshots {
+                let sent = serde_json::to_string(&snapshot)
+                    .map_err(Error::Serialization)
+                    .and_then(|ser| broadcaster.send(ser).map_err(Error::WebSocket));
+
+                if let Err(err) = sent {
+                    println!("Skipped streaming tick {} to spectators: {:?}", snapshot.tick, err);
+                }
+            }
+        });
+
+        let listener = try!(TcpListener::bind(http_addr).map_err(Error::Io));
+        let page = Arc::new(VIEWER_PAGE.replace("WS_PORT", &ws_addr.port().to_string()));
+
+        // Each request is answered on its own thread, so that one slow client
+        // doesn't hold up the others.
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if let Ok(stream) = stream {
+                    let page = page.clone();
+                    thread::spawn(move || serve_page(stream, &page));
+                }
+            }
+        });
+
+        Ok(Server { http_addr: http_addr })
+    }
+
+    /// The address spectators should open in their browsers.
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.http_addr)
+    }
+}
+
+/// Respond to an HTTP request with the viewer page, whatever was asked for.
+fn serve_page(stream: TcpStream, page: &str) -> io::Result<()> {
+    let mut reader = BufReader::new(try!(stream.try_clone()));
+
+    // Skip the request, up to the blank line after its headers.
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if try!(reader.read_line(&mut line)) == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut stream = stream;
+    try!(write!(stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+                page.len()));
+    try!(stream.write_all(page.as_bytes()));
+    stream.flush()
+}
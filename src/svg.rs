@@ -0,0 +1,226 @@
+//! Drawing a snapshot of the world as an SVG image, for reports and design
+//! docs. Robots are drawn with the same shapes and colours as `DrawState`.
+
+use math::*;
+use config::Config;
+use snapshot::Snapshot;
+use replay::BotInfo;
+use render::{self, BODY_SHAPE, RADAR_SHAPE, GUN_SHAPE, BULLET_SHAPE, GUN_SHADE, BORDER_COLOR};
+
+use std::f64::consts::PI;
+use std::io::{self, Write};
+
+/// How many pixels across each world unit is, when the image is shown at its
+/// natural size.
+const PIXELS_PER_UNIT: f64 = 8.0;
+
+/// The opacity of radar arcs and trails.
+const RADAR_ARC_OPACITY: f64 = 0.12;
+const TRAIL_OPACITY: f64 = 0.5;
+
+/// The width of the border and trail lines, in world units.
+const LINE_WIDTH: f64 = 0.1;
+
+/// The size of robots' names, and how far above their robots they are drawn,
+/// in world units.
+const NAME_SIZE: f64 = 1.0;
+const NAME_OFFSET: f64 = 1.5;
+
+/// What to draw besides the snapshot itself.
+#[derive(Debug, Clone, Default)]
+pub struct Extras {
+    /// Each robot's recent positions, oldest first. Robots without any have
+    /// no trail.
+    pub trails: Vec<Vec<Vector2>>,
+
+    /// Each robot's radar heading on the tick before the snapshot, to show the
+    /// arc swept since. Robots without one have no arc.
+    pub prev_radar_headings: Vec<f64>,
+}
+
+/// Write an SVG image of the arena in a snapshot. The robots' infos, used for
+/// their names and colours, must be in world order.
+pub fn write_svg<W: Write>(mut writer: W,
+                           config: &Config,
+                           bots: &[BotInfo],
+                           snapshot: &Snapshot,
+                           extras: &Extras)
+                           -> io::Result<()> {
+    let (width, height) = (config.world_size.x, config.world_size.y);
+
+    let colors: Vec<_> = snapshot.bots
+        .iter()
+        .enumerate()
+        .map(|(i, bot)| {
+            let base = bots.get(i).map_or([1.0, 1.0, 1.0], |info| render::base_color(i, info));
+            render::bot_color(bot, base)
+        })
+        .collect();
+
+    try!(writeln!(writer,
+                  "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                   viewBox=\"0 0 {} {}\">",
+                  width * PIXELS_PER_UNIT,
+                  height * PIXELS_PER_UNIT,
+                  width,
+                  height));
+
+    try!(writeln!(writer,
+                  "<rect width=\"{}\" height=\"{}\" fill=\"black\" stroke=\"{}\" \
+                   stroke-width=\"{}\"/>",
+                  width,
+                  height,
+                  svg_color(BORDER_COLOR),
+                  LINE_WIDTH));
+
+    // The world's y axis points up, but SVG's points down.
+    try!(writeln!(writer,
+                  "<g transform=\"translate(0 {}) scale(1 -1)\">",
+                  height));
+
+    for (trail, &color) in extras.trails.iter().zip(&colors) {
+        if trail.len() < 2 {
+            continue;
+        }
+
+        let points: Vec<_> = trail.iter().map(|pos| format!("{},{}", pos.x, pos.y)).collect();
+
+        try!(writeln!(writer,
+                      "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" \
+                       stroke-width=\"{}\" stroke-opacity=\"{}\"/>",
+                      points.join(" "),
+                      svg_color(color),
+                      LINE_WIDTH,
+                      TRAIL_OPACITY));
+    }
+
+    for ((bot, &color), &from) in snapshot.bots
+        .iter()
+        .zip(&colors)
+        .zip(&extras.prev_radar_headings) {
+        if bot.hit_points <= 0.0 || from == bot.radar_heading {
+            continue;
+        }
+
+        try!(write_radar_arc(&mut writer,
+                             bot.pos,
+                             from,
+                             bot.radar_heading,
+                             config.radar_range,
+                             color));
+    }
+
+    for (bot, &color) in snapshot.bots.iter().zip(&colors) {
+        let shaded = [color[0] * GUN_SHADE, color[1] * GUN_SHADE, color[2] * GUN_SHADE, 1.0];
+        let light = render::lighten(color);
+
+        try!(write_shape(&mut writer, &BODY_SHAPE, bot.pos, bot.heading, 1.0, color));
+        try!(write_shape(&mut writer, &GUN_SHAPE, bot.pos, bot.gun_heading, 1.0, shaded));
+        try!(write_shape(&mut writer, &RADAR_SHAPE, bot.pos, bot.radar_heading, 1.0, light));
+    }
+
+    for bullet in &snapshot.bullets {
+        let color = colors.get(bullet.owner).map_or([1.0; 4], |&color| render::lighten(color));
+        let size = render::bullet_size(bullet.power) as f64;
+
+        try!(write_shape(&mut writer, &BULLET_SHAPE, bullet.pos, 0.0, size, color));
+    }
+
+    try!(writeln!(writer, "</g>"));
+
+    // Names are drawn outside the flipped group, so they are the right way up.
+    for (i, (bot, &color)) in snapshot.bots.iter().zip(&colors).enumerate() {
+        let label = bots.get(i)
+            .map_or(i.to_string(), |info| format!("{} {}", info.id, info.name()));
+
+        try!(writeln!(writer,
+                      "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"monospace\" \
+                       text-anchor=\"middle\" fill=\"{}\">{}</text>",
+                      bot.pos.x,
+                      height - bot.pos.y - NAME_OFFSET,
+                      NAME_SIZE,
+                      svg_color(color),
+                      escape(&label)));
+    }
+
+    writeln!(writer, "</svg>")
+}
+
+/// Write one of the shapes from `render`, scaled, rotated to a heading and
+/// moved to a position in the world.
+fn write_shape<W: Write>(writer: &mut W,
+                         shape: &[[f32; 2]],
+                         pos: Vector2,
+                         heading: f64,
+                         size: f64,
+                         color: [f32; 4])
+                         -> io::Result<()> {
+    let points: Vec<_> = shape.iter().map(|point| format!("{},{}", point[0], point[1])).collect();
+
+    writeln!(writer,
+             "<polygon points=\"{}\" fill=\"{}\" \
+              transform=\"translate({} {}) rotate({}) scale({})\"/>",
+             points.join(" "),
+             svg_color(color),
+             pos.x,
+             pos.y,
+             heading.to_degrees(),
+             size)
+}
+
+/// Write the arc swept by a robot's radar between two headings.
+fn write_radar_arc<W: Write>(writer: &mut W,
+                             pos: Vector2,
+                             from: f64,
+                             to: f64,
+                             range: f64,
+                             color: [f32; 4])
+                             -> io::Result<()> {
+    let (start, end) = if from <= to { (from, to) } else { (to, from) };
+
+    // A whole turn or more would start and end at the same point, which SVG
+    // can't draw as an arc.
+    if end - start >= 2.0 * PI {
+        return writeln!(writer,
+                        "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+                        pos.x,
+                        pos.y,
+                        range,
+                        svg_color(color),
+                        RADAR_ARC_OPACITY);
+    }
+
+    let point = |angle: f64| pos + Vector2::new(angle.cos(), angle.sin()) * range;
+    let (a, b) = (point(start), point(end));
+    let large_arc = if end - start > PI { 1 } else { 0 };
+
+    writeln!(writer,
+             "<path d=\"M {} {} L {} {} A {} {} 0 {} 1 {} {} Z\" fill=\"{}\" \
+              fill-opacity=\"{}\"/>",
+             pos.x,
+             pos.y,
+             a.x,
+             a.y,
+             range,
+             range,
+             large_arc,
+             b.x,
+             b.y,
+             svg_color(color),
+             RADAR_ARC_OPACITY)
+}
+
+/// Format a colour, with components between 0 and 1, for SVG.
+fn svg_color(color: [f32; 4]) -> String {
+    let component = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+
+    format!("rgb({},{},{})",
+            component(color[0]),
+            component(color[1]),
+            component(color[2]))
+}
+
+/// Escape the characters which are special in SVG text.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,333 @@
+//! A text-mode viewer, which draws the arena as a grid of characters in a
+//! terminal using ANSI escape codes.
+
+use math::*;
+use rpc::BotState;
+use config::Config;
+use snapshot::Snapshot;
+use replay::BotInfo;
+use render::{base_color, bot_color};
+use view::{Input, Viewer};
+
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The glyph for a robot facing each eighth of a turn, anticlockwise from
+/// east.
+const HEADING_GLYPHS: [char; 8] = ['→', '↗', '↑', '↖', '←', '↙', '↓', '↘'];
+
+const DEAD_GLYPH: char = 'x';
+const BULLET_GLYPH: char = '·';
+
+/// The width of a robot's name on the scoreboard, in characters.
+const NAME_WIDTH: usize = 12;
+
+/// The commands typed into the terminal, each followed by Return.
+pub const TEXT_COMMANDS: &'static str = "Return: pause, .: step, ,: step back, +/-: speed, \
+                                         ]/[: next/previous hit, a number: jump to tick, \
+                                         r: reload scripts, q: quit";
+
+/// Shows a match in the terminal. It is redrawn at a fixed rate, and commands
+/// are read from standard input a line at a time.
+pub struct TextViewer {
+    snapshots: Receiver<Snapshot>,
+
+    /// The latest snapshot received.
+    current: Snapshot,
+
+    config: Config,
+
+    /// Each robot's name and its colour when it hasn't chosen its own, in
+    /// world order.
+    names: Vec<String>,
+    base_colors: Vec<[f32; 3]>,
+
+    /// The size of the arena, in characters.
+    width: usize,
+    height: usize,
+
+    frame_duration: Duration,
+
+    /// Lines typed into the terminal, read on another thread so that drawing
+    /// isn't blocked waiting for them.
+    lines: Receiver<String>,
+}
+
+/// A command typed into the terminal.
+enum Command {
+    Input(Input),
+    Quit,
+}
+
+impl TextViewer {
+    /// Create a viewer which draws the latest of the given snapshots as an
+    /// arena the given number of characters across and down, redrawn the
+    /// given number of times a second. The robots' infos must be in world
+    /// order.
+    pub fn new(snapshots: Receiver<Snapshot>,
+               config: &Config,
+               bots: &[BotInfo],
+               (width, height): (usize, usize),
+               frames_per_second: u32)
+               -> Self {
+        let (line_sender, lines) = mpsc::channel();
+
+        thread::spawn(move || {
+            let stdin = io::stdin();
+
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if line_sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        TextViewer {
+            snapshots: snapshots,
+            current: Snapshot::default(),
+            config: config.clone(),
+            names: bots.iter().map(|bot| bot.name().to_owned()).collect(),
+            base_colors: bots.iter().enumerate().map(|(i, bot)| base_color(i, bot)).collect(),
+            width: width,
+            height: height,
+            frame_duration: Duration::new(0, 1_000_000_000 / frames_per_second.max(1)),
+            lines: lines,
+        }
+    }
+
+    /// Take in every snapshot received since the last frame.
+    fn update(&mut self) {
+        while let Ok(snapshot) = self.snapshots.try_recv() {
+            self.current = snapshot;
+        }
+    }
+
+    /// The colour to draw the robot with the given index in, as in the
+    /// window.
+    fn bot_color(&self, index: usize, bot: &BotState) -> [f32; 3] {
+        let base = self.base_colors.get(index).cloned().unwrap_or([1.0, 1.0, 1.0]);
+        let color = bot_color(bot, base);
+
+        [color[0], color[1], color[2]]
+    }
+
+    /// The row and column of the cell containing a world position, if it is
+    /// in the arena.
+    fn cell(&self, pos: Vector2) -> Option<(usize, usize)> {
+        let x = pos.x / self.config.world_size.x;
+        let y = 1.0 - pos.y / self.config.world_size.y;
+
+        if x < 0.0 || x >= 1.0 || y < 0.0 || y >= 1.0 {
+            return None;
+        }
+
+        Some(((y * self.height as f64) as usize, (x * self.width as f64) as usize))
+    }
+
+    /// Build the whole frame as text, to be written in one go. Bullets are
+    /// drawn first, so robots are shown over them.
+    fn frame(&self) -> String {
+        let snapshot = &self.current;
+
+        let mut grid = vec![vec![(' ', None); self.width]; self.height];
+
+        for bullet in &snapshot.bullets {
+            if let Some((row, col)) = self.cell(bullet.pos) {
+                let color = snapshot.bots
+                    .get(bullet.owner)
+                    .map(|bot| self.bot_color(bullet.owner, bot));
+                grid[row][col] = (BULLET_GLYPH, color);
+            }
+        }
+
+        for (i, bot) in snapshot.bots.iter().enumerate() {
+            if let Some((row, col)) = self.cell(bot.pos) {
+                // Living robots are drawn over dead ones in the same cell.
+                if bot.hit_points <= 0.0 && grid[row][col].0 != ' ' {
+                    continue;
+                }
+
+                let glyph = if bot.hit_points > 0.0 {
+                    heading_glyph(bot.heading)
+                } else {
+                    DEAD_GLYPH
+                };
+
+                grid[row][col] = (glyph, Some(self.bot_color(i, bot)));
+            }
+        }
+
+        let scoreboard = self.scoreboard();
+        let border = format!("+{}+", (0..self.width).map(|_| '-').collect::<String>());
+
+        // Draw over the last frame from the top left, rather than clearing the
+        // screen, to avoid flicker.
+        let mut out = String::from("\x1b[H");
+
+        out.push_str(&border);
+        out.push_str(&format!("  Tick {}\x1b[K\n", snapshot.tick));
+
+        for (row, cells) in grid.iter().enumerate() {
+            out.push('|');
+
+            let mut last_color = None;
+            for &(glyph, color) in cells {
+                if color != last_color {
+                    out.push_str(&match color {
+                        Some(color) => ansi_color(color),
+                        None => "\x1b[0m".to_owned(),
+                    });
+                    last_color = color;
+                }
+
+                out.push(glyph);
+            }
+
+            out.push_str("\x1b[0m|  ");
+
+            if let Some(line) = scoreboard.get(row) {
+                out.push_str(line);
+            }
+
+            out.push_str("\x1b[0m\x1b[K\n");
+        }
+
+        out.push_str(&border);
+        out.push_str("\x1b[K\n\x1b[J");
+
+        out
+    }
+
+    /// The lines of the scoreboard shown beside the arena, with robots sorted
+    /// by hit points. Only as many robots as fit beside the arena are shown.
+    fn scoreboard(&self) -> Vec<String> {
+        let snapshot = &self.current;
+
+        let mut order: Vec<usize> = (0..snapshot.bots.len()).collect();
+        order.sort_by(|&a, &b| {
+            match snapshot.bots[b].hit_points.partial_cmp(&snapshot.bots[a].hit_points) {
+                Some(Ordering::Equal) | None => a.cmp(&b),
+                Some(ordering) => ordering,
+            }
+        });
+
+        let mut lines = vec![format!("{:>4} {:<width$} {:>5} {:>5}",
+                                     "#",
+                                     "Name",
+                                     "HP",
+                                     "Kills",
+                                     width = NAME_WIDTH)];
+
+        for i in order.into_iter().take(self.height.saturating_sub(1)) {
+            let bot = &snapshot.bots[i];
+            let name: String = self.names
+                .get(i)
+                .map_or("", |name| name.as_str())
+                .chars()
+                .take(NAME_WIDTH)
+                .collect();
+            let kills = snapshot.scores.get(i).map_or(0, |score| score.kills);
+
+            lines.push(format!("{}{:>4} {:<width$} {:>5.0} {:>5}",
+                               ansi_color(self.bot_color(i, bot)),
+                               i,
+                               name,
+                               bot.hit_points,
+                               kills,
+                               width = NAME_WIDTH));
+        }
+
+        lines
+    }
+}
+
+impl Viewer for TextViewer {
+    /// Draw the arena until `q` is typed. Other typed commands are turned into
+    /// control input.
+    fn run(&mut self, handle_input: &mut FnMut(Input)) {
+        let stdout = io::stdout();
+
+        // Clear the screen and hide the cursor while drawing.
+        print!("\x1b[2J\x1b[?25l");
+
+        'frames: loop {
+            let frame_start = Instant::now();
+
+            self.update();
+
+            {
+                let mut out = stdout.lock();
+                let _ = out.write_all(self.frame().as_bytes());
+                let _ = out.flush();
+            }
+
+            loop {
+                match self.lines.try_recv() {
+                    Ok(line) => {
+                        match parse_command(&line) {
+                            Some(Command::Input(input)) => handle_input(input),
+                            Some(Command::Quit) => break 'frames,
+                            None => {}
+                        }
+                    }
+
+                    // Without standard input, the viewer just keeps drawing.
+                    Err(TryRecvError::Empty) |
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+
+            let elapsed = frame_start.elapsed();
+            if elapsed < self.frame_duration {
+                thread::sleep(self.frame_duration - elapsed);
+            }
+        }
+
+        // Put the terminal back how it was.
+        print!("\x1b[0m\x1b[?25h");
+        let _ = stdout.lock().flush();
+    }
+}
+
+/// The command for a line typed into the terminal, if it is one.
+fn parse_command(line: &str) -> Option<Command> {
+    let input = match line.trim() {
+        "" | "p" => Input::TogglePause,
+        "." => Input::StepForward,
+        "," => Input::StepBack,
+        "+" | "=" => Input::Faster,
+        "-" => Input::Slower,
+        "]" => Input::NextEvent,
+        "[" => Input::PrevEvent,
+        "r" => Input::Reload,
+        "q" => return Some(Command::Quit),
+        tick => return tick.parse().ok().map(|tick| Command::Input(Input::Seek(tick))),
+    };
+
+    Some(Command::Input(input))
+}
+
+/// The glyph pointing closest to the given heading.
+fn heading_glyph(heading: f64) -> char {
+    let eighth = (heading / (PI / 4.0)).round() as i64 % 8;
+    HEADING_GLYPHS[((eighth + 8) % 8) as usize]
+}
+
+/// The escape code to draw text in a colour, with components between 0 and 1.
+fn ansi_color(color: [f32; 3]) -> String {
+    let component = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+
+    format!("\x1b[38;2;{};{};{}m",
+            component(color[0]),
+            component(color[1]),
+            component(color[2]))
+}
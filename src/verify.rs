@@ -0,0 +1,136 @@
+//! Comparing re-run matches against their recordings.
+
+use snapshot::*;
+use rpc::BotState;
+
+use serde_json;
+
+use std::fmt;
+
+/// The first place where a re-run tick differs from the recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// The recorded tick which differs.
+    pub tick: u64,
+
+    /// The path to the field which differs, e.g. `bots[3].heading`.
+    pub field: String,
+
+    /// The recorded value, formatted for display.
+    pub expected: String,
+
+    /// The re-run value, formatted for display.
+    pub actual: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f,
+               "Diverged at tick {}: {} was {} in the replay but {} when re-run",
+               self.tick,
+               self.field,
+               self.expected,
+               self.actual)
+    }
+}
+
+/// Compare two fields, returning the `Difference` from the enclosing function
+/// if they aren't equal.
+macro_rules! compare {
+    ($tick:expr, $field:expr, $expected:expr, $actual:expr) => {
+        if $expected != $actual {
+            return Some(Difference {
+                tick: $tick,
+                field: $field,
+                expected: format!("{:?}", $expected),
+                actual: format!("{:?}", $actual),
+            });
+        }
+    }
+}
+
+/// Pass a snapshot through the same serialisation used by replay files, so
+/// that it can be compared exactly with one that was read from a replay.
+pub fn as_recorded(snapshot: &Snapshot) -> Snapshot {
+    let ser = serde_json::to_string(snapshot).unwrap();
+    serde_json::from_str(ser.as_str()).unwrap()
+}
+
+/// Find the first field in which the re-run snapshot differs from the recorded
+/// one, if any.
+pub fn first_difference(expected: &Snapshot, actual: &Snapshot) -> Option<Difference> {
+    let tick = expected.tick;
+
+    compare!(tick, "tick".to_owned(), expected.tick, actual.tick);
+
+    compare!(tick, "bots.len()".to_owned(), expected.bots.len(), actual.bots.len());
+    for (i, (e, a)) in expected.bots.iter().zip(&actual.bots).enumerate() {
+        if let Some(diff) = bot_difference(tick, i, e, a) {
+            return Some(diff);
+        }
+    }
+
+    compare!(tick, "bullets.len()".to_owned(), expected.bullets.len(), actual.bullets.len());
+    for (i, (e, a)) in expected.bullets.iter().zip(&actual.bullets).enumerate() {
+        compare!(tick, format!("bullets[{}].owner", i), e.owner, a.owner);
+        compare!(tick, format!("bullets[{}].pos", i), e.pos, a.pos);
+        compare!(tick, format!("bullets[{}].vel", i), e.vel, a.vel);
+        compare!(tick, format!("bullets[{}].power", i), e.power, a.power);
+    }
+
+    compare!(tick, "events.len()".to_owned(), expected.events.len(), actual.events.len());
+    for (i, (e, a)) in expected.events.iter().zip(&actual.events).enumerate() {
+        compare!(tick, format!("events[{}]", i), *e, *a);
+    }
+
+    compare!(tick, "scores.len()".to_owned(), expected.scores.len(), actual.scores.len());
+    for (i, (e, a)) in expected.scores.iter().zip(&actual.scores).enumerate() {
+        compare!(tick, format!("scores[{}]", i), *e, *a);
+    }
+
+    compare!(tick, "drawings.len()".to_owned(), expected.drawings.len(), actual.drawings.len());
+    for (i, (e, a)) in expected.drawings.iter().zip(&actual.drawings).enumerate() {
+        compare!(tick, format!("drawings[{}]", i), *e, *a);
+    }
+
+    // Latency depends on the machine, so it isn't compared.
+    compare!(tick,
+             "diagnostics.len()".to_owned(),
+             expected.diagnostics.len(),
+             actual.diagnostics.len());
+    for (i, (e, a)) in expected.diagnostics.iter().zip(&actual.diagnostics).enumerate() {
+        compare!(tick, format!("diagnostics[{}].errors", i), e.errors, a.errors);
+        compare!(tick, format!("diagnostics[{}].strikes", i), e.strikes, a.strikes);
+        compare!(tick, format!("diagnostics[{}].last_error", i), e.last_error, a.last_error);
+        compare!(tick,
+                 format!("diagnostics[{}].dropped_drawings", i),
+                 e.dropped_drawings,
+                 a.dropped_drawings);
+        compare!(tick,
+                 format!("diagnostics[{}].disqualified", i),
+                 e.disqualified,
+                 a.disqualified);
+    }
+
+    None
+}
+
+fn bot_difference(tick: u64, i: usize, e: &BotState, a: &BotState) -> Option<Difference> {
+    compare!(tick, format!("bots[{}].pos", i), e.pos, a.pos);
+    compare!(tick, format!("bots[{}].heading", i), e.heading, a.heading);
+    compare!(tick, format!("bots[{}].gun_heading", i), e.gun_heading, a.gun_heading);
+    compare!(tick, format!("bots[{}].radar_heading", i), e.radar_heading, a.radar_heading);
+    compare!(tick, format!("bots[{}].speed", i), e.speed, a.speed);
+    compare!(tick, format!("bots[{}].thrust", i), e.thrust, a.thrust);
+    compare!(tick, format!("bots[{}].turn_rate", i), e.turn_rate, a.turn_rate);
+    compare!(tick, format!("bots[{}].gun_turn_rate", i), e.gun_turn_rate, a.gun_turn_rate);
+    compare!(tick,
+             format!("bots[{}].radar_turn_rate", i),
+             e.radar_turn_rate,
+             a.radar_turn_rate);
+    compare!(tick, format!("bots[{}].hit_points", i), e.hit_points, a.hit_points);
+    compare!(tick, format!("bots[{}].shoot_power", i), e.shoot_power, a.shoot_power);
+    compare!(tick, format!("bots[{}].color", i), e.color, a.color);
+
+    None
+}
@@ -0,0 +1,30 @@
+//! Ways of showing a match, either live or from a replay. Every viewer draws
+//! the latest of a stream of world snapshots, and turns the user's input into
+//! the same controls.
+
+/// A request from the user to control the match being shown. Live matches
+/// can't go backwards or jump around, so they ignore some of these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    TogglePause,
+    StepForward,
+    StepBack,
+    Faster,
+    Slower,
+    NextEvent,
+    PrevEvent,
+
+    /// Jump to the given tick. `u64::max_value()` jumps to the end.
+    Seek(u64),
+
+    /// Reload the robots which are read from source as the match runs, like
+    /// scripts.
+    Reload,
+}
+
+/// Something which shows a match.
+pub trait Viewer {
+    /// Show the match until the user closes the viewer, passing each control
+    /// input to `handle_input`.
+    fn run(&mut self, handle_input: &mut FnMut(Input));
+}
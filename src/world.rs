@@ -1,35 +1,97 @@
 use robo::Robo;
 use ctl::RoboCtl;
 use config::*;
+use math::*;
+use rpc::BotState;
+use snapshot::*;
+use physics::duration_float;
 
 use std::sync::{Arc, Mutex, RwLock, Barrier, RwLockReadGuard};
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::mpsc::{Receiver, Sender, SyncSender, TrySendError, channel, sync_channel};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use std::fmt::Debug;
+use std::mem;
+use std::f64::consts::PI;
 
-pub struct Bullet {
-    pub pos: Vector2,
-    pub vel: Vector2,
-    pub power: f64,
+/// The slowest allowed simulation speed, as a multiple of real time.
+pub const MIN_SPEED: f64 = 0.25;
+
+/// The fastest allowed simulation speed, as a multiple of real time.
+pub const MAX_SPEED: f64 = 16.0;
+
+/// A command which can be sent to a running `World` to control its timing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    /// Pause if running, or run if paused.
+    TogglePause,
+
+    /// Pause and run a single tick.
+    Step,
+
+    /// Double the simulation speed, up to `MAX_SPEED`.
+    Faster,
+
+    /// Halve the simulation speed, down to `MIN_SPEED`.
+    Slower,
+
+    /// Reload the robots which can be changed while running, between ticks.
+    /// Ignored in deterministic mode, which has to play out the same way.
+    Reload,
 }
 
-pub struct WorldData<Ctl: RoboCtl> {
-    robos_data: Vec<Ctl::PublicData>,
-    bullets: Vec<Bullet>,
+/// Somewhere the world sends each tick's snapshot.
+enum Observer {
+    /// Sent every snapshot, however far behind it falls.
+    All(Sender<Snapshot>),
+
+    /// Sent snapshots only while it has room for them, so that a slow
+    /// observer drops snapshots rather than using more and more memory.
+    Lossy(SyncSender<Snapshot>),
+}
+
+impl Observer {
+    /// Send a snapshot, returning whether the observer is still listening.
+    fn send(&self, snapshot: &Snapshot) -> bool {
+        match *self {
+            Observer::All(ref sender) => sender.send(snapshot.clone()).is_ok(),
+            Observer::Lossy(ref sender) => {
+                match sender.try_send(snapshot.clone()) {
+                    Ok(()) | Err(TrySendError::Full(_)) => true,
+                    Err(TrySendError::Disconnected(_)) => false,
+                }
+            }
+        }
+    }
 }
 
 /// A world in which a robot battle takes place.
 pub struct World<Ctl: RoboCtl> {
     all_robos: Vec<Arc<Robo<Ctl>>>,
-    data: Arc<Mutex<WorldData<Ctl>>>,
+    robos_data: Arc<Mutex<Snapshot>>,
+    bullets: Vec<Bullet>,
+    scores: Vec<Score>,
+
+    /// Each robot's radar heading at the end of the last tick, to find the arc
+    /// swept by its radar since.
+    radar_headings: Vec<f64>,
+
+    /// Which robots have been reported as disqualified.
+    disqualified: Vec<bool>,
+
+    tick: u64,
+    observers: Vec<Observer>,
+    realtime: bool,
+    paused: bool,
+    speed: f64,
     tick_lock: Arc<TickLock>,
     config: Config,
     stop_receiver: Receiver<()>,
-    bullets_in: Receiver<Bullet>,
+    control_sender: Sender<Control>,
+    control_receiver: Receiver<Control>,
 }
 
-impl<Ctl: RoboCtl + Debug> World<Ctl> {
+impl<Ctl: RoboCtl<PublicData = BotState> + Debug> World<Ctl> {
     /// Create a new world with the given configuration and robot controllers.
     /// The world will stop if it ever receives a message over the provided
     /// sender.
@@ -40,17 +102,39 @@ impl<Ctl: RoboCtl + Debug> World<Ctl> {
             robos.into_iter().map(|ctl| Arc::new(Robo::new(config.clone(), ctl))).collect();
 
         let (stop_sender, stop_receiver) = channel();
+        let (control_sender, control_receiver) = channel();
 
         let tick_lock = Arc::new(TickLock::new(all_robos.len()));
 
+        let bots: Vec<_> = all_robos.iter()
+            .map(|robo| robo.with_ctl(|ctl| ctl.public_data().clone()).unwrap())
+            .collect();
+
+        let scores = vec![Score::default(); bots.len()];
+        let radar_headings = bots.iter().map(|bot| bot.radar_heading).collect();
+        let disqualified = vec![false; bots.len()];
+
         (World {
-            robos_data: Arc::new(Mutex::new(all_robos.iter()
-                .map(|robo| robo.with_ctl(|ctl| ctl.public_data().clone()).unwrap())
-                .collect())),
+            robos_data: Arc::new(Mutex::new(Snapshot {
+                bots: bots,
+                scores: scores.clone(),
+                ..Snapshot::default()
+            })),
 
             all_robos: all_robos,
+            bullets: Vec::new(),
+            scores: scores,
+            radar_headings: radar_headings,
+            disqualified: disqualified,
+            tick: 0,
+            observers: Vec::new(),
+            realtime: true,
+            paused: false,
+            speed: 1.0,
             config: config,
             stop_receiver: stop_receiver,
+            control_sender: control_sender,
+            control_receiver: control_receiver,
             tick_lock: tick_lock.clone(),
         },
          tick_lock,
@@ -62,11 +146,43 @@ impl<Ctl: RoboCtl + Debug> World<Ctl> {
         self.all_robos.as_slice()
     }
 
-    /// Get the mutex-protected robot data so that it can be drawn.
-    pub fn robos_data(&self) -> Arc<Mutex<Vec<Ctl::PublicData>>> {
+    /// Get the mutex-protected snapshot of the latest tick so that it can be
+    /// drawn.
+    pub fn robos_data(&self) -> Arc<Mutex<Snapshot>> {
         self.robos_data.clone()
     }
 
+    /// Receive a snapshot of every tick from now on, for example to record
+    /// the match. Should be called before the world starts running so that no
+    /// ticks are missed. Dropping the receiver stops the snapshots.
+    pub fn observe(&mut self) -> Receiver<Snapshot> {
+        let (sender, receiver) = channel();
+        self.observers.push(Observer::All(sender));
+        receiver
+    }
+
+    /// Like `observe`, but snapshots are dropped while `capacity` of them are
+    /// waiting to be received. This suits observers which may not keep up,
+    /// such as ones which draw every snapshot.
+    pub fn observe_lossy(&mut self, capacity: usize) -> Receiver<Snapshot> {
+        let (sender, receiver) = sync_channel(capacity);
+        self.observers.push(Observer::Lossy(sender));
+        receiver
+    }
+
+    /// Get a sender which can be used to pause, step and change the speed of
+    /// the world while it is running.
+    pub fn controller(&self) -> Sender<Control> {
+        self.control_sender.clone()
+    }
+
+    /// Set whether to wait between ticks to keep to the configured tick rate.
+    /// This is on by default. Turning it off runs the simulation as fast as
+    /// the robots can keep up, which is mostly useful in deterministic mode.
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+    }
+
     /// Synchronously runs a world. Each of the contained robots must already be
     /// running independently and concurrently, or this will not make progress.
     ///
@@ -76,37 +192,297 @@ impl<Ctl: RoboCtl + Debug> World<Ctl> {
         let tick_dur = self.config.tick_duration();
 
         let mut next_tick_time = Instant::now() + tick_dur;
+        let mut last_tick_time = Instant::now();
+        let mut elapsed = Duration::new(0, 0);
+        let mut stopping = false;
 
         loop {
             // Do things safe in the knowledge that robots don't have locks on
-            // their own state
-            {
-                let mut robos_data = self.robos_data.lock().unwrap();
-
-                *robos_data = self.all_robos
-                    .iter()
-                    .map(|robo| robo.with_ctl(|ctl| ctl.public_data().clone()).unwrap())
-                    .collect();
+            // their own state. Bullets move by the same time the robots were
+            // given for the last tick.
+            let events = self.update(duration_float(elapsed));
+
+            let mut drawings = Vec::new();
+            let mut diagnostics = Vec::new();
+
+            let bots: Vec<BotState> = self.all_robos
+                .iter()
+                .enumerate()
+                .map(|(i, robo)| {
+                    robo.with_ctl(|ctl| {
+                            drawings.extend(ctl.debug_drawings().iter().map(|drawing| {
+                                Drawing {
+                                    bot: i,
+                                    shape: drawing.shape.clone(),
+                                    color: drawing.color,
+                                }
+                            }));
+
+                            diagnostics.push(ctl.diagnostics().clone());
+                            ctl.public_data().clone()
+                        })
+                        .unwrap()
+                })
+                .collect();
+
+            let snapshot = Snapshot {
+                tick: self.tick,
+                bots: bots,
+                bullets: self.bullets.clone(),
+                events: events,
+                scores: self.scores.clone(),
+                drawings: drawings,
+                diagnostics: diagnostics,
+            };
+
+            self.observers.retain(|observer| observer.send(&snapshot));
+
+            *self.robos_data.lock().unwrap() = snapshot;
+            self.tick += 1;
+
+            // While paused, hold the robots back by not taking the tick lock.
+            let mut was_paused = false;
+
+            loop {
+                if let Ok(_) = self.stop_receiver.try_recv() {
+                    stopping = true;
+                }
+
+                let stepping = self.apply_controls();
+                if stopping || stepping || !self.paused {
+                    break;
+                }
+
+                was_paused = true;
+                thread::sleep(tick_dur);
+            }
+
+            // Work out how much simulated time passes in this tick. Ticks
+            // straight after a pause, or while stepping, are a single tick
+            // long so that robots don't jump.
+            let now_time = Instant::now();
+            let tick_wait = scale_duration(tick_dur, 1.0 / self.speed);
+
+            elapsed = if self.config.deterministic || was_paused || self.paused {
+                tick_dur
+            } else {
+                scale_duration(now_time.duration_since(last_tick_time), self.speed)
+            };
+
+            if was_paused {
+                next_tick_time = now_time + tick_wait;
             }
 
+            last_tick_time = now_time;
+            self.tick_lock.set_elapsed(elapsed);
+
             // Allow robots to make progress
             if let Some(mut tick_guard) = self.tick_lock.take() {
-                if let Ok(_) = self.stop_receiver.try_recv() {
+                if stopping {
                     tick_guard.stop();
                 }
 
                 // Handle timing. Current implementation will never catch back up after
                 // losing frames. Consider implementing that.
-                let now_time = Instant::now();
-                if now_time < next_tick_time {
-                    thread::sleep(next_tick_time - now_time);
+                if self.realtime {
+                    let now_time = Instant::now();
+                    if now_time < next_tick_time {
+                        thread::sleep(next_tick_time - now_time);
+                    }
+                    next_tick_time += tick_wait;
                 }
-                next_tick_time += tick_dur;
             } else {
                 return;
             }
         }
     }
+
+    /// Apply any controls which have been sent. Returns whether a single step
+    /// was requested.
+    fn apply_controls(&mut self) -> bool {
+        let mut stepping = false;
+
+        while let Ok(control) = self.control_receiver.try_recv() {
+            match control {
+                Control::TogglePause => self.paused = !self.paused,
+                Control::Step => {
+                    self.paused = true;
+                    stepping = true;
+                }
+                Control::Faster => self.speed = (self.speed * 2.0).min(MAX_SPEED),
+                Control::Slower => self.speed = (self.speed / 2.0).max(MIN_SPEED),
+                Control::Reload => {
+                    if !self.config.deterministic {
+                        for robo in &self.all_robos {
+                            robo.with_ctl_mut(|ctl| ctl.reload()).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+
+        stepping
+    }
+
+    /// Collect what the robots did on the last tick and tell them what their
+    /// radars swept over, then move bullets the given number of seconds and
+    /// resolve any hits. Returns everything that happened.
+    fn update(&mut self, elapsed: f64) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        {
+            let bullets = &mut self.bullets;
+            let reported = &mut self.disqualified;
+
+            for (i, robo) in self.all_robos.iter().enumerate() {
+                robo.with_ctl_mut(|ctl| {
+                        for mut bullet in ctl.take_bullets() {
+                            bullet.owner = i;
+                            events.push(Event::Shot {
+                                bot: i,
+                                power: bullet.power,
+                            });
+                            bullets.push(bullet);
+                        }
+
+                        let responses = ctl.take_responses();
+                        if !responses.is_empty() {
+                            events.push(Event::Responded {
+                                bot: i,
+                                responses: responses,
+                            });
+                        }
+
+                        for msg in ctl.take_debug_prints() {
+                            events.push(Event::DebugPrint { bot: i, msg: msg });
+                        }
+
+                        for error in ctl.take_strikes() {
+                            events.push(Event::Strike { bot: i, error: error });
+                        }
+
+                        if ctl.diagnostics().disqualified && !reported[i] {
+                            reported[i] = true;
+                            events.push(Event::Disqualified { bot: i });
+                        }
+                    })
+                    .unwrap();
+            }
+        }
+
+        let bots: Vec<BotState> = self.all_robos
+            .iter()
+            .map(|robo| robo.with_ctl(|ctl| ctl.public_data().clone()).unwrap())
+            .collect();
+
+        // Tell robots about everything their radar swept over.
+        for (i, bot) in bots.iter().enumerate() {
+            if bot.hit_points <= 0.0 {
+                continue;
+            }
+
+            let from = self.radar_headings.get(i).cloned().unwrap_or(bot.radar_heading);
+
+            for (target, other) in bots.iter().enumerate() {
+                if target == i || other.hit_points <= 0.0 ||
+                   !in_radar_arc(bot.pos,
+                                 from,
+                                 bot.radar_heading,
+                                 self.config.radar_range,
+                                 other.pos) {
+                    continue;
+                }
+
+                self.all_robos[i].with_ctl_mut(|ctl| ctl.scan(other.pos)).unwrap();
+                events.push(Event::Scan {
+                    bot: i,
+                    target: target,
+                });
+            }
+        }
+
+        self.radar_headings = bots.iter().map(|bot| bot.radar_heading).collect();
+
+        let radius = self.config.robot_radius;
+        let world_size = self.config.world_size;
+        let mut hits = Vec::new();
+
+        let bullets = mem::replace(&mut self.bullets, Vec::new());
+
+        for mut bullet in bullets {
+            bullet.pos += bullet.vel * elapsed;
+
+            let target = bots.iter().enumerate().position(|(i, bot)| {
+                i != bullet.owner && bot.hit_points > 0.0 &&
+                (bot.pos - bullet.pos).length() <= radius
+            });
+
+            if let Some(target) = target {
+                hits.push((target, bullet));
+            } else if in_bounds(bullet.pos, world_size) {
+                self.bullets.push(bullet);
+            }
+        }
+
+        for (target, bullet) in hits {
+            let died = self.all_robos[target]
+                .with_ctl_mut(|ctl| {
+                    let was_alive = ctl.public_data().hit_points > 0.0;
+                    ctl.hit(bullet.power);
+                    was_alive && ctl.public_data().hit_points <= 0.0
+                })
+                .unwrap();
+
+            events.push(Event::Hit {
+                bot: target,
+                shooter: bullet.owner,
+                damage: bullet.power,
+            });
+
+            self.scores[bullet.owner].damage_dealt += bullet.power;
+            self.scores[target].damage_taken += bullet.power;
+
+            if died {
+                self.scores[bullet.owner].kills += 1;
+                events.push(Event::Death { bot: target });
+            }
+        }
+
+        events
+    }
+}
+
+/// Whether `target` is within `range` of a radar at `pos`, and within the arc
+/// it swept turning from the heading `from` to `to`. Headings are in radians,
+/// and aren't limited to a single turn.
+fn in_radar_arc(pos: Vector2, from: f64, to: f64, range: f64, target: Vector2) -> bool {
+    let offset = target - pos;
+    if offset.length() > range {
+        return false;
+    }
+
+    let (start, end) = if from <= to { (from, to) } else { (to, from) };
+    let width = end - start;
+
+    if width >= 2.0 * PI {
+        return true;
+    }
+
+    // The angle from the start of the arc to the target, anticlockwise.
+    let angle = offset.y.atan2(offset.x);
+    let from_start = (angle - start) % (2.0 * PI);
+    let from_start = if from_start < 0.0 { from_start + 2.0 * PI } else { from_start };
+
+    from_start <= width
+}
+
+fn in_bounds(pos: Vector2, size: Vector2) -> bool {
+    0.0 <= pos.x && pos.x <= size.x && 0.0 <= pos.y && pos.y <= size.y
+}
+
+fn scale_duration(d: Duration, factor: f64) -> Duration {
+    let secs = duration_float(d) * factor;
+    Duration::new(secs as u64, (secs.fract() * 1_000_000_000.0) as u32)
 }
 
 // =============================================================================
@@ -118,6 +494,10 @@ impl<Ctl: RoboCtl + Debug> World<Ctl> {
 pub struct TickLock {
     barrier: Barrier,
     is_running: RwLock<bool>,
+
+    // The simulated time that passes during the current tick. Only the world
+    // writes this, between ticks.
+    elapsed: Mutex<Duration>,
 }
 
 /// An RAII guard indicating that we are in the process of ticking. The tick
@@ -132,6 +512,8 @@ pub struct TickGuard<'a> {
     // A guard on the `is_running` lock is kept so that the world can't
     // be stopped while some threads are still ticking.
     running_guard: Option<RwLockReadGuard<'a, bool>>,
+
+    elapsed: Duration,
 }
 
 impl TickLock {
@@ -139,9 +521,17 @@ impl TickLock {
         TickLock {
             barrier: Barrier::new(size + 1),
             is_running: RwLock::new(true),
+            elapsed: Mutex::new(Duration::new(0, 0)),
         }
     }
 
+    /// Set the simulated time that passes during the next tick. This is
+    /// private because only the world can decide it, and must only be done
+    /// between ticks.
+    fn set_elapsed(&self, elapsed: Duration) {
+        *self.elapsed.lock().unwrap() = elapsed;
+    }
+
     /// Try to take the lock. If the world is running, wait until we are allowed
     /// to tick and return the RAII guard. Otherwise, return `None`.
     pub fn take(&self) -> Option<TickGuard> {
@@ -162,11 +552,17 @@ impl TickLock {
             barrier: &self.barrier,
             is_running: &self.is_running,
             running_guard: Some(running),
+            elapsed: *self.elapsed.lock().unwrap(),
         })
     }
 }
 
 impl<'a> TickGuard<'a> {
+    /// The simulated time that passes during this tick.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
     /// Stop the world. This will wait until all other threads are done with
     /// their current `TickGuard`s, then make sure no `TickGuard`s can be taken
     /// in the future. This is private because only the world can stop itself.
@@ -191,3 +587,102 @@ impl<'a> Drop for TickGuard<'a> {
         self.barrier.wait();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use ctl::user::{Brain, Ctl};
+    use math::*;
+    use rpc::{BotState, Message, Response};
+    use snapshot::Event;
+
+    use std::sync::{Arc, Mutex};
+
+    /// A brain which remembers every message it is sent.
+    struct Recorder {
+        msgs: Arc<Mutex<Vec<Message>>>,
+    }
+
+    impl Brain for Recorder {
+        fn respond(&mut self, _state: BotState, msg: Message) -> Result<Vec<Response>, String> {
+            self.msgs.lock().unwrap().push(msg);
+            Ok(Vec::new())
+        }
+    }
+
+    /// A brain which counts the times it is reloaded.
+    struct Reloadable {
+        reloads: Arc<Mutex<u32>>,
+    }
+
+    impl Brain for Reloadable {
+        fn respond(&mut self, _state: BotState, _msg: Message) -> Result<Vec<Response>, String> {
+            Ok(Vec::new())
+        }
+
+        fn reload(&mut self) {
+            *self.reloads.lock().unwrap() += 1;
+        }
+    }
+
+    fn reloads_after_control(config: Config) -> u32 {
+        let reloads = Arc::new(Mutex::new(0));
+        let brain = Reloadable { reloads: reloads.clone() };
+        let ctl = Ctl::with_brain(0, Vector2::new(100.0, 100.0), config.clone(), brain);
+        let (mut world, _, _) = World::new(config, vec![ctl]);
+
+        world.controller().send(Control::Reload).unwrap();
+        world.apply_controls();
+
+        let count = *reloads.lock().unwrap();
+        count
+    }
+
+    fn recording_ctl(config: &Config, pos: Vector2) -> (Ctl, Arc<Mutex<Vec<Message>>>) {
+        let msgs = Arc::new(Mutex::new(Vec::new()));
+        let ctl = Ctl::with_brain(0, pos, config.clone(), Recorder { msgs: msgs.clone() });
+        (ctl, msgs)
+    }
+
+    fn scans(msgs: &Mutex<Vec<Message>>) -> Vec<Vector2> {
+        msgs.lock()
+            .unwrap()
+            .iter()
+            .filter_map(|msg| {
+                match *msg {
+                    Message::Scan { scan_pos } => Some(scan_pos),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn robots_in_the_radar_arc_are_scanned() {
+        let config = Config::default();
+
+        // Radars start facing along the x axis, so only the first robot's
+        // points at the other.
+        let (seer, seer_msgs) = recording_ctl(&config, Vector2::new(100.0, 100.0));
+        let (seen, seen_msgs) = recording_ctl(&config, Vector2::new(120.0, 100.0));
+        let (mut world, _, _) = World::new(config, vec![seer, seen]);
+
+        let events = world.update(0.0);
+
+        assert_eq!(scans(&seer_msgs), vec![Vector2::new(120.0, 100.0)]);
+        assert!(scans(&seen_msgs).is_empty());
+        assert!(events.contains(&Event::Scan { bot: 0, target: 1 }));
+    }
+
+    #[test]
+    fn reloading_reaches_the_brain() {
+        assert_eq!(reloads_after_control(Config::default()), 1);
+    }
+
+    #[test]
+    fn reloading_is_ignored_in_deterministic_mode() {
+        let config = Config { deterministic: true, ..Config::default() };
+        assert_eq!(reloads_after_control(config), 0);
+    }
+}
@@ -0,0 +1,409 @@
+//! Higher-level actions for robots to queue up on their `RoboHook`, which are
+//! turned into turn rates and thrust step by step, within the configured
+//! limits, until they are done.
+//!
+//! The body's turning, its driving, the gun and the radar each have their own
+//! queue, so a robot can, say, turn and drive at once while aiming its gun.
+//! Each queue carries out one action at a time, in the order they were queued,
+//! and the robot's `action_done` is called as each finishes. While a queue has
+//! an action, it sets that part's rate at the end of every step, overriding
+//! anything set by hand.
+
+use config::Config;
+use math::*;
+use rpc::BotState;
+use targeting::{normalize_angle, predict};
+
+use std::collections::VecDeque;
+
+/// How close, in radians, a heading must be to count as reached.
+const ANGLE_TOLERANCE: f64 = 0.01;
+
+/// How close, in units, a move must come to its distance to count as done.
+const DISTANCE_TOLERANCE: f64 = 0.05;
+
+/// Identifies a queued action, so that its completion can be recognised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ActionId(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Turn the body to face an absolute heading.
+    TurnTo(f64),
+
+    /// Drive the given distance, backwards if negative.
+    Move(f64),
+
+    /// Turn the gun to face an absolute bearing.
+    AimGun(f64),
+
+    /// Turn the radar to face a position.
+    PointRadarAt(Vector2),
+}
+
+/// The rates that carry out the current actions, for the parts that have one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Commands {
+    pub turn_rate: Option<f64>,
+    pub thrust: Option<f64>,
+    pub gun_turn_rate: Option<f64>,
+    pub radar_turn_rate: Option<f64>,
+}
+
+/// The actions queued for each part of a robot.
+#[derive(Debug, Default)]
+pub struct Actions {
+    next_id: u64,
+    turn: Queue,
+    drive: Queue,
+    gun: Queue,
+    radar: Queue,
+}
+
+/// The actions queued for one part of a robot.
+#[derive(Debug, Default)]
+struct Queue {
+    actions: VecDeque<Queued>,
+
+    /// Whether the last action has just finished, so the part should stop.
+    stopping: bool,
+}
+
+#[derive(Debug)]
+struct Queued {
+    id: ActionId,
+    action: Action,
+
+    /// For moves, how far has been driven so far, and where the robot was at
+    /// the last step.
+    travelled: f64,
+    last_pos: Option<Vector2>,
+}
+
+impl Actions {
+    /// Queue an action after any others for the same part.
+    pub fn push(&mut self, action: Action) -> ActionId {
+        let id = ActionId(self.next_id);
+        self.next_id += 1;
+
+        let queued = Queued {
+            id: id,
+            action: action,
+            travelled: 0.0,
+            last_pos: None,
+        };
+
+        let queue = match action {
+            Action::TurnTo(_) => &mut self.turn,
+            Action::Move(_) => &mut self.drive,
+            Action::AimGun(_) => &mut self.gun,
+            Action::PointRadarAt(_) => &mut self.radar,
+        };
+
+        queue.actions.push_back(queued);
+        id
+    }
+
+    /// Drop every queued action, stopping the parts that were busy.
+    pub fn clear(&mut self) {
+        for queue in self.queues_mut().iter_mut() {
+            queue.stopping = !queue.actions.is_empty();
+            queue.actions.clear();
+        }
+    }
+
+    /// The number of actions queued, including those under way.
+    pub fn len(&self) -> usize {
+        self.turn.actions.len() + self.drive.actions.len() + self.gun.actions.len() +
+        self.radar.actions.len()
+    }
+
+    /// Whether there are no actions queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check the actions under way against the robot's state, returning those
+    /// that have finished, in the order they were queued.
+    pub fn update(&mut self, state: &BotState) -> Vec<(ActionId, Action)> {
+        let mut done = Vec::new();
+
+        for queue in self.queues_mut().iter_mut() {
+            while let Some(finished) = queue.update(state) {
+                done.push(finished);
+            }
+        }
+
+        done.sort_by_key(|&(id, _)| id);
+        done
+    }
+
+    /// The rates that carry out the actions under way, given the robot's state
+    /// at the start of the step and its state with any rates the controller
+    /// has set since.
+    pub fn commands(&mut self, config: &Config, start: &BotState, state: &BotState) -> Commands {
+        let step_seconds = config.step_seconds();
+
+        // Rates set now only take effect at the next step, and until then the
+        // robot carries on at the rates it had, so aim from where it will be.
+        let next = predict(config, start, config.ticks_per_step);
+
+        let turn_rate = self.turn.command(|queued| {
+            match queued.action {
+                Action::TurnTo(heading) => {
+                    let offset = normalize_angle(heading - next.heading);
+                    config.turn_rate_limits.clamp(offset / step_seconds)
+                }
+                _ => 0.0,
+            }
+        });
+
+        // The gun and radar turn with the body, which they make up for, at
+        // whatever rate it will turn from the next step.
+        let body_turn_rate = turn_rate.unwrap_or(state.turn_rate);
+
+        let thrust = self.drive.command(|queued| {
+            if queued.last_pos.is_none() {
+                queued.last_pos = Some(start.pos);
+            }
+
+            match queued.action {
+                Action::Move(distance) => {
+                    let driving = signed_distance(start.pos, &next);
+                    drive_thrust(config, &next, distance - queued.travelled - driving)
+                }
+                _ => 0.0,
+            }
+        });
+
+        let gun_turn_rate = self.gun.command(|queued| {
+            match queued.action {
+                Action::AimGun(bearing) => {
+                    let offset = normalize_angle(bearing - next.gun_heading);
+                    config.gun_turn_rate_limits.clamp(offset / step_seconds - body_turn_rate)
+                }
+                _ => 0.0,
+            }
+        });
+
+        let radar_turn_rate = self.radar.command(|queued| {
+            match queued.action {
+                Action::PointRadarAt(pos) => {
+                    let to = pos - next.pos;
+                    let offset = normalize_angle(to.y.atan2(to.x) - next.radar_heading);
+                    config.radar_turn_rate_limits.clamp(offset / step_seconds - body_turn_rate)
+                }
+                _ => 0.0,
+            }
+        });
+
+        Commands {
+            turn_rate: turn_rate,
+            thrust: thrust,
+            gun_turn_rate: gun_turn_rate,
+            radar_turn_rate: radar_turn_rate,
+        }
+    }
+
+    fn queues_mut(&mut self) -> [&mut Queue; 4] {
+        [&mut self.turn, &mut self.drive, &mut self.gun, &mut self.radar]
+    }
+}
+
+impl Queue {
+    /// Pop the action under way if it has finished.
+    fn update(&mut self, state: &BotState) -> Option<(ActionId, Action)> {
+        let finished = match self.actions.front_mut() {
+            Some(queued) => queued.update(state),
+            None => return None,
+        };
+
+        if !finished {
+            return None;
+        }
+
+        let queued = self.actions.pop_front().unwrap();
+        self.stopping = self.actions.is_empty();
+
+        Some((queued.id, queued.action))
+    }
+
+    /// The rate for the action under way, zero if the part should stop, or
+    /// `None` if it is free.
+    fn command<F>(&mut self, rate: F) -> Option<f64>
+        where F: FnOnce(&mut Queued) -> f64
+    {
+        if let Some(queued) = self.actions.front_mut() {
+            return Some(rate(queued));
+        }
+
+        if self.stopping {
+            self.stopping = false;
+            Some(0.0)
+        } else {
+            None
+        }
+    }
+}
+
+impl Queued {
+    /// Take the robot's progress into account, returning whether the action
+    /// has finished.
+    fn update(&mut self, state: &BotState) -> bool {
+        match self.action {
+            Action::TurnTo(heading) => reached(heading, state.heading),
+            Action::AimGun(bearing) => reached(bearing, state.gun_heading),
+            Action::PointRadarAt(pos) => {
+                let to = pos - state.pos;
+                reached(to.y.atan2(to.x), state.radar_heading)
+            }
+            Action::Move(distance) => {
+                // Progress is measured along the path driven, forwards or
+                // backwards, so moves still finish if the robot turns.
+                if let Some(last_pos) = self.last_pos {
+                    self.travelled += signed_distance(last_pos, state);
+                }
+
+                self.last_pos = Some(state.pos);
+                (distance - self.travelled).abs() <= DISTANCE_TOLERANCE
+            }
+        }
+    }
+}
+
+/// The thrust to drive the remaining distance, which is negative if it is
+/// behind. Thrust is cut once friction would stop the robot in time.
+fn drive_thrust(config: &Config, state: &BotState, remaining: f64) -> f64 {
+    // How far the robot would coast with no thrust, as friction slows it each
+    // tick.
+    let coast = if config.drive_friction < 1.0 {
+        state.speed * config.tick_seconds() * config.drive_friction /
+        (1.0 - config.drive_friction)
+    } else {
+        0.0
+    };
+
+    let short = remaining - coast;
+
+    if short > DISTANCE_TOLERANCE {
+        config.thrust_limits.max
+    } else if short < -DISTANCE_TOLERANCE {
+        config.thrust_limits.min
+    } else {
+        0.0
+    }
+}
+
+/// How far a robot has driven from a position, negative if it is reversing.
+fn signed_distance(from: Vector2, state: &BotState) -> f64 {
+    let driven = (state.pos - from).length();
+
+    if state.speed < 0.0 {
+        -driven
+    } else {
+        driven
+    }
+}
+
+/// Whether a heading is close enough to a target heading.
+fn reached(target: f64, heading: f64) -> bool {
+    normalize_angle(target - heading).abs() <= ANGLE_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use harness::Harness;
+    use math::*;
+    use robo_controller::{RoboController, RoboHook};
+
+    use std::f64::consts::PI;
+
+    /// Queues the given actions when initialised, and records which finish.
+    struct Queuer {
+        actions: Vec<Action>,
+        done: Vec<Action>,
+    }
+
+    impl RoboController for Queuer {
+        fn init(&mut self, hook: &mut RoboHook) {
+            for action in &self.actions {
+                hook.queue(*action);
+            }
+        }
+
+        fn action_done(&mut self, _hook: &mut RoboHook, _id: ActionId, action: Action) {
+            self.done.push(action);
+        }
+    }
+
+    /// Aims its gun a little way round while starting its body turning itself.
+    struct Turner;
+
+    impl RoboController for Turner {
+        fn init(&mut self, hook: &mut RoboHook) {
+            let bearing = hook.config().step_seconds();
+            hook.queue(Action::AimGun(bearing));
+        }
+
+        fn step(&mut self, hook: &mut RoboHook, _elapsed: f64) {
+            let turn_rate = hook.config().turn_rate_limits.max;
+            hook.set_turn_rate(turn_rate);
+        }
+    }
+
+    fn run(actions: Vec<Action>, steps: u32) -> Harness<Queuer> {
+        let config = Config::default();
+        let queuer = Queuer {
+            actions: actions,
+            done: Vec::new(),
+        };
+
+        let mut harness = Harness::new(queuer, config, Vector2::new(50.0, 50.0));
+        harness.run_steps(steps);
+        harness
+    }
+
+    #[test]
+    fn turns_in_order() {
+        let actions = vec![Action::TurnTo(PI / 2.0), Action::TurnTo(-PI / 4.0)];
+        let harness = run(actions.clone(), 100);
+
+        assert_eq!(harness.controller().done, actions);
+        assert!((harness.state().heading + PI / 4.0).abs() < 0.05);
+        assert_eq!(harness.state().turn_rate, 0.0);
+        assert!(harness.errors().is_empty());
+    }
+
+    #[test]
+    fn moves_the_distance() {
+        let harness = run(vec![Action::Move(5.0)], 200);
+
+        assert_eq!(harness.controller().done, vec![Action::Move(5.0)]);
+        assert!((harness.state().pos.x - 55.0).abs() < 0.2);
+        assert!(harness.errors().is_empty());
+    }
+
+    #[test]
+    fn aiming_makes_up_for_turn_rates_set_in_the_step() {
+        let config = Config::default();
+        let mut harness = Harness::new(Turner, config.clone(), Vector2::new(50.0, 50.0));
+
+        // The gun turns at the rates set in the first step from the second, by
+        // when the body has started turning too.
+        harness.run_steps(3);
+        assert!((harness.state().gun_heading - config.step_seconds()).abs() < 0.01);
+        assert!(harness.errors().is_empty());
+    }
+
+    #[test]
+    fn parts_act_at_once() {
+        let actions = vec![Action::AimGun(PI), Action::PointRadarAt(Vector2::new(50.0, 0.0))];
+        let harness = run(actions, 100);
+
+        assert_eq!(harness.controller().done.len(), 2);
+        assert!((normalize_angle(harness.state().gun_heading - PI)).abs() < 0.05);
+        assert!((normalize_angle(harness.state().radar_heading + PI / 2.0)).abs() < 0.05);
+    }
+}
@@ -0,0 +1,67 @@
+//! Drives in wide circles, keeping its radar spinning, and shoots where its
+//! target will be if it keeps turning the way it is.
+
+use math::*;
+use robo_controller::*;
+
+use super::*;
+
+pub struct CircularGunner {
+    /// Seconds since the start of the match.
+    time: f64,
+    tracker: Tracker,
+}
+
+impl CircularGunner {
+    pub fn new() -> Self {
+        CircularGunner {
+            time: 0.0,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+impl RoboController for CircularGunner {
+    fn init(&mut self, hook: &mut RoboHook) {
+        let config = hook.config().clone();
+
+        hook.set_thrust(config.thrust_limits.max / 2.0);
+        hook.set_turn_rate(config.turn_rate_limits.min / 4.0);
+        hook.set_radar_turn_rate(config.radar_turn_rate_limits.max);
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, elapsed: f64) {
+        self.time += elapsed;
+
+        let target = match self.tracker.target(self.time) {
+            Some(target) => target,
+            None => return,
+        };
+
+        // Bullets can't reach beyond the radar's range in much more than this.
+        let config = hook.config().clone();
+        let max_time = 2.0 * config.radar_range / config.bullet_speed;
+        let next = next_state(hook);
+
+        // Shots are fired from where the robot will be at the next step, by
+        // when the target will have turned too.
+        let step = config.step_seconds();
+        let target_pos = target.pos_at(self.time + step);
+        let target_vel = rotate(target.vel, target.turn_rate * step);
+
+        let aim = circular_intercept(next.pos,
+                                     target_pos,
+                                     target_vel,
+                                     target.turn_rate,
+                                     config.bullet_speed,
+                                     config.tick_seconds(),
+                                     max_time)
+            .unwrap_or(target_pos);
+
+        aim_and_shoot(hook, &next, aim);
+    }
+
+    fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {
+        self.tracker.scan(hook.pos, scan_pos, self.time);
+    }
+}
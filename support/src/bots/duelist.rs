@@ -0,0 +1,115 @@
+//! Locks its radar onto one robot, sweeping it back and forth across it so
+//! that it is scanned every step, and strafes it at a set distance, reversing
+//! now and then, while shooting where it will be if it keeps going straight.
+
+use math::*;
+use robo_controller::*;
+
+use super::*;
+
+use std::f64::consts::PI;
+
+/// How far past the target the radar sweeps, in radians.
+const RADAR_OVERSHOOT: f64 = 0.1;
+
+/// The distance it tries to keep from its target.
+const PREFERRED_DISTANCE: f64 = 15.0;
+
+/// How many steps it strafes in one direction before reversing.
+const STEPS_PER_REVERSE: u32 = 20;
+
+/// How close to the edge it can get before turning back to the middle.
+const EDGE_MARGIN: f64 = 5.0;
+
+pub struct Duelist {
+    /// Seconds since the start of the match.
+    time: f64,
+    tracker: Tracker,
+
+    /// Which way round the target it is strafing, 1 for anticlockwise and -1
+    /// for clockwise.
+    direction: f64,
+    steps_until_reverse: u32,
+}
+
+impl Duelist {
+    pub fn new() -> Self {
+        Duelist {
+            time: 0.0,
+            tracker: Tracker::default(),
+            direction: 1.0,
+            steps_until_reverse: STEPS_PER_REVERSE,
+        }
+    }
+}
+
+impl RoboController for Duelist {
+    fn init(&mut self, hook: &mut RoboHook) {
+        let radar_turn_rate = hook.config().radar_turn_rate_limits.max;
+        hook.set_radar_turn_rate(radar_turn_rate);
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, elapsed: f64) {
+        self.time += elapsed;
+
+        self.steps_until_reverse -= 1;
+        if self.steps_until_reverse == 0 {
+            self.steps_until_reverse = STEPS_PER_REVERSE;
+            self.direction = -self.direction;
+        }
+
+        let config = hook.config().clone();
+        let next = next_state(hook);
+        let pos = next.pos;
+
+        let target = match self.tracker.target(self.time) {
+            Some(target) => target,
+            None => {
+                // Search for a target, heading for the middle where there are
+                // most likely to be some.
+                let turn_rate = turn_towards(&config, &next, bearing(pos, centre(&config)));
+                hook.set_turn_rate(turn_rate);
+                hook.set_thrust(config.thrust_limits.max / 2.0);
+                hook.set_gun_turn_rate(0.0);
+                hook.set_radar_turn_rate(config.radar_turn_rate_limits.max);
+                return;
+            }
+        };
+
+        let target_pos = target.pos_at(self.time + config.step_seconds());
+        let to_target = bearing(pos, target_pos);
+        let distance = (target_pos - pos).length();
+
+        // Strafe across the target, angled in to close the distance or out to
+        // open it.
+        let closing = ((distance - PREFERRED_DISTANCE) / PREFERRED_DISTANCE).max(-1.0).min(1.0);
+        let heading = if near_edge(&config, pos, EDGE_MARGIN) {
+            bearing(pos, centre(&config))
+        } else {
+            to_target + self.direction * (PI / 2.0 - closing * PI / 4.0)
+        };
+
+        let turn_rate = turn_towards(&config, &next, heading);
+        hook.set_turn_rate(turn_rate);
+        hook.set_thrust(config.thrust_limits.max);
+
+        // Sweep the radar just past the target, so that next step it sweeps
+        // back over it the other way.
+        let radar_offset = angle_between(next.radar_heading, to_target);
+        let overshoot = if radar_offset >= 0.0 {
+            RADAR_OVERSHOOT
+        } else {
+            -RADAR_OVERSHOOT
+        };
+        let radar_turn_rate = radar_towards(&config, &next, to_target + overshoot, hook.turn_rate);
+        hook.set_radar_turn_rate(radar_turn_rate);
+
+        let aim = linear_intercept(pos, target_pos, target.vel, config.bullet_speed)
+            .unwrap_or(target_pos);
+        aim_and_shoot(hook, &next, aim);
+    }
+
+    fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {
+        self.tracker.scan(hook.pos, scan_pos, self.time);
+    }
+}
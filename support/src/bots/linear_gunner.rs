@@ -0,0 +1,55 @@
+//! Drives in wide circles, keeping its radar spinning, and shoots where its
+//! target will be if it keeps going in a straight line.
+
+use math::*;
+use robo_controller::*;
+
+use super::*;
+
+pub struct LinearGunner {
+    /// Seconds since the start of the match.
+    time: f64,
+    tracker: Tracker,
+}
+
+impl LinearGunner {
+    pub fn new() -> Self {
+        LinearGunner {
+            time: 0.0,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+impl RoboController for LinearGunner {
+    fn init(&mut self, hook: &mut RoboHook) {
+        let config = hook.config().clone();
+
+        hook.set_thrust(config.thrust_limits.max / 2.0);
+        hook.set_turn_rate(config.turn_rate_limits.max / 4.0);
+        hook.set_radar_turn_rate(config.radar_turn_rate_limits.max);
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, elapsed: f64) {
+        self.time += elapsed;
+
+        let target = match self.tracker.target(self.time) {
+            Some(target) => target,
+            None => return,
+        };
+
+        let config = hook.config().clone();
+        let next = next_state(hook);
+
+        // Shots are fired from where the robot will be at the next step.
+        let target_pos = target.pos_at(self.time + config.step_seconds());
+        let aim = linear_intercept(next.pos, target_pos, target.vel, config.bullet_speed)
+            .unwrap_or(target_pos);
+
+        aim_and_shoot(hook, &next, aim);
+    }
+
+    fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {
+        self.tracker.scan(hook.pos, scan_pos, self.time);
+    }
+}
@@ -0,0 +1,212 @@
+//! Robots that come with the crate, which can be run as processes or hosted
+//! directly in the simulator. Besides the spinner, they are reference
+//! opponents, from easy to hard, for benchmarking other robots against:
+//!
+//! - `sitting_duck` never moves or shoots.
+//! - `wall_hugger` drives round the edge of the arena, shooting inwards.
+//! - `random_walker` wanders about, shooting straight at whatever it sees.
+//! - `linear_gunner` shoots where robots will be if they keep going straight.
+//! - `circular_gunner` shoots where robots will be if they keep turning.
+//! - `duelist` locks its radar onto one robot, strafing it while shooting.
+
+use config::Config;
+use math::*;
+use robo_controller::{RoboController, RoboHook};
+use rpc::BotState;
+use targeting::*;
+
+pub mod spinner;
+pub mod sitting_duck;
+pub mod wall_hugger;
+pub mod random_walker;
+pub mod linear_gunner;
+pub mod circular_gunner;
+pub mod duelist;
+
+/// The names of the robots that come with the crate.
+pub const NAMES: &'static [&'static str] = &["spinner",
+                                             "sitting_duck",
+                                             "wall_hugger",
+                                             "random_walker",
+                                             "linear_gunner",
+                                             "circular_gunner",
+                                             "duelist"];
+
+/// The names of the reference opponents, from easiest to hardest.
+pub const OPPONENTS: &'static [&'static str] = &["sitting_duck",
+                                                 "wall_hugger",
+                                                 "random_walker",
+                                                 "linear_gunner",
+                                                 "circular_gunner",
+                                                 "duelist"];
+
+/// Create a fresh controller for the robot with the given name, if there is
+/// one.
+pub fn by_name(name: &str) -> Option<Box<RoboController + Send>> {
+    match name {
+        "spinner" => Some(Box::new(spinner::Spinner::new())),
+        "sitting_duck" => Some(Box::new(sitting_duck::SittingDuck)),
+        "wall_hugger" => Some(Box::new(wall_hugger::WallHugger::new())),
+        "random_walker" => Some(Box::new(random_walker::RandomWalker::new())),
+        "linear_gunner" => Some(Box::new(linear_gunner::LinearGunner::new())),
+        "circular_gunner" => Some(Box::new(circular_gunner::CircularGunner::new())),
+        "duelist" => Some(Box::new(duelist::Duelist::new())),
+        _ => None,
+    }
+}
+
+/// How long a target can go unseen before it is given up on, in seconds.
+const STALE_TARGET_SECONDS: f64 = 1.0;
+
+/// How far a target can be from where it was expected and still be taken for
+/// the same robot, plus how far it could have driven since, per second.
+const SAME_TARGET_DISTANCE: f64 = 0.5;
+const SAME_TARGET_SPEED: f64 = 5.0;
+
+/// What has been worked out about an enemy from scanning it.
+#[derive(Debug, Clone, Copy)]
+struct Track {
+    pos: Vector2,
+    vel: Vector2,
+
+    /// How fast the direction of its velocity is turning.
+    turn_rate: f64,
+
+    /// When it was last scanned, in seconds since the start of the match.
+    time: f64,
+}
+
+/// Keeps track of a single enemy between scans. Scans don't say which robot
+/// was seen, so scans near where the target is expected are taken to be of
+/// it, and the target only changes to another robot when it goes unseen for a
+/// while or the other is closer.
+#[derive(Debug, Default)]
+struct Tracker {
+    track: Option<Track>,
+}
+
+impl Track {
+    /// Where the target is expected to be at a given time, if it keeps going
+    /// straight.
+    fn pos_at(&self, time: f64) -> Vector2 {
+        self.pos + self.vel * (time - self.time)
+    }
+}
+
+impl Tracker {
+    /// Take a scan into account. `own_pos` is the scanning robot's position.
+    fn scan(&mut self, own_pos: Vector2, scan_pos: Vector2, time: f64) {
+        let new_track = Track {
+            pos: scan_pos,
+            vel: Vector2::zero(),
+            turn_rate: 0.0,
+            time: time,
+        };
+
+        let track = match self.track {
+            Some(track) => track,
+            None => {
+                self.track = Some(new_track);
+                return;
+            }
+        };
+
+        let elapsed = time - track.time;
+        let expected = track.pos_at(time);
+        let gate = SAME_TARGET_DISTANCE + SAME_TARGET_SPEED * elapsed;
+
+        if (scan_pos - expected).length() <= gate {
+            // Several scans of the same robot between steps can't say how it
+            // is moving.
+            if elapsed <= 0.0 {
+                return;
+            }
+
+            let vel = (scan_pos - track.pos) * (1.0 / elapsed);
+            let turn_rate = if vel.length() > 0.0 && track.vel.length() > 0.0 {
+                angle_between(angle_of(track.vel), angle_of(vel)) / elapsed
+            } else {
+                0.0
+            };
+
+            self.track = Some(Track {
+                pos: scan_pos,
+                vel: vel,
+                turn_rate: turn_rate,
+                time: time,
+            });
+        } else if elapsed > STALE_TARGET_SECONDS ||
+                  (scan_pos - own_pos).length() < (expected - own_pos).length() {
+            self.track = Some(new_track);
+        }
+    }
+
+    /// The target, unless it has gone unseen for too long.
+    fn target(&self, time: f64) -> Option<Track> {
+        match self.track {
+            Some(track) if time - track.time <= STALE_TARGET_SECONDS => Some(track),
+            _ => None,
+        }
+    }
+}
+
+/// Where the robot will be at the next step, when the rates it sets now take
+/// effect. Steering is worked out from there.
+fn next_state(hook: &RoboHook) -> BotState {
+    let config = hook.config();
+    predict(config, hook, config.ticks_per_step)
+}
+
+/// The turn rate that brings the body round to face a heading a step after
+/// `next`.
+fn turn_towards(config: &Config, next: &BotState, heading: f64) -> f64 {
+    angle_between(next.heading, heading) / config.step_seconds()
+}
+
+/// The gun turn rate that brings the gun round to face a heading a step after
+/// `next`, making up for the body turning at the given rate.
+fn gun_towards(config: &Config, next: &BotState, heading: f64, turn_rate: f64) -> f64 {
+    angle_between(next.gun_heading, heading) / config.step_seconds() - turn_rate
+}
+
+/// The radar turn rate that brings the radar round to face a heading a step
+/// after `next`, making up for the body turning at the given rate.
+fn radar_towards(config: &Config, next: &BotState, heading: f64, turn_rate: f64) -> f64 {
+    angle_between(next.radar_heading, heading) / config.step_seconds() - turn_rate
+}
+
+/// Turn the gun towards a point, shooting if it will be pointing there when
+/// the shot is fired, at the next step. The body's turn rate should already be
+/// set.
+fn aim_and_shoot(hook: &mut RoboHook, next: &BotState, aim: Vector2) {
+    let config = hook.config().clone();
+    let heading = bearing(next.pos, aim);
+    let distance = (aim - next.pos).length();
+    let tolerance = config.robot_radius.atan2(distance);
+
+    if angle_between(next.gun_heading, heading).abs() <= tolerance {
+        if let Some(power) = recommended_power(&config, next, distance) {
+            hook.shoot(power);
+        }
+    }
+
+    let gun_turn_rate = gun_towards(&config, next, heading, hook.turn_rate);
+    hook.set_gun_turn_rate(gun_turn_rate);
+}
+
+/// A vector turned anticlockwise by an angle.
+fn rotate(v: Vector2, angle: f64) -> Vector2 {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Whether a point is within a margin of the edge of the arena.
+fn near_edge(config: &Config, pos: Vector2, margin: f64) -> bool {
+    pos.x < margin || pos.y < margin || pos.x > config.world_size.x - margin ||
+    pos.y > config.world_size.y - margin
+}
+
+/// The middle of the arena.
+fn centre(config: &Config) -> Vector2 {
+    config.world_size * 0.5
+}
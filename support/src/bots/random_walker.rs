@@ -0,0 +1,105 @@
+//! Wanders about in random directions at random speeds, keeping its radar
+//! spinning and shooting straight at whatever it sees.
+
+use math::*;
+use robo_controller::*;
+
+use super::*;
+
+use std::f64::consts::PI;
+
+/// How many steps it keeps going in the same direction.
+const STEPS_PER_WANDER: u32 = 30;
+
+/// How close to the edge it can get before turning back to the middle.
+const EDGE_MARGIN: f64 = 5.0;
+
+pub struct RandomWalker {
+    /// Seeded from the starting position, so that deterministic matches can be
+    /// re-run exactly.
+    rng: XorShift,
+    steps_until_wander: u32,
+    heading: f64,
+    thrust: f64,
+
+    /// Seconds since the start of the match.
+    time: f64,
+    tracker: Tracker,
+}
+
+/// A small, fast random number generator.
+struct XorShift(u64);
+
+impl XorShift {
+    /// A random number between 0 and 1.
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl RandomWalker {
+    pub fn new() -> Self {
+        RandomWalker {
+            rng: XorShift(1),
+            steps_until_wander: 0,
+            heading: 0.0,
+            thrust: 0.0,
+            time: 0.0,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+impl RoboController for RandomWalker {
+    fn init(&mut self, hook: &mut RoboHook) {
+        let seed = (hook.pos.x * 1e6) as u64 ^ ((hook.pos.y * 1e6) as u64).rotate_left(32);
+        self.rng = XorShift(seed | 1);
+
+        let radar_turn_rate = hook.config().radar_turn_rate_limits.max;
+        hook.set_radar_turn_rate(radar_turn_rate);
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, elapsed: f64) {
+        self.time += elapsed;
+
+        let config = hook.config().clone();
+        let next = next_state(hook);
+        let pos = next.pos;
+
+        if self.steps_until_wander == 0 {
+            self.steps_until_wander = STEPS_PER_WANDER;
+
+            let limits = &config.thrust_limits;
+            self.heading = self.rng.next_f64() * 2.0 * PI - PI;
+            self.thrust = limits.min + (limits.max - limits.min) * self.rng.next_f64();
+        }
+
+        self.steps_until_wander -= 1;
+
+        let (heading, thrust) = if near_edge(&config, pos, EDGE_MARGIN) {
+            (bearing(pos, centre(&config)), config.thrust_limits.max)
+        } else {
+            (self.heading, self.thrust)
+        };
+
+        let turn_rate = turn_towards(&config, &next, heading);
+        hook.set_turn_rate(turn_rate);
+        hook.set_thrust(thrust);
+
+        match self.tracker.target(self.time) {
+            Some(target) => {
+                let fire_time = self.time + config.step_seconds();
+                aim_and_shoot(hook, &next, target.pos_at(fire_time))
+            }
+            None => hook.set_gun_turn_rate(0.0),
+        }
+    }
+
+    fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {
+        self.tracker.scan(hook.pos, scan_pos, self.time);
+    }
+}
@@ -0,0 +1,7 @@
+//! Never moves or shoots. Anything should be able to beat it.
+
+use robo_controller::RoboController;
+
+pub struct SittingDuck;
+
+impl RoboController for SittingDuck {}
@@ -0,0 +1,44 @@
+//! Drives in circles with its gun turning the other way, switching between
+//! thrusting forwards and backwards every so often.
+
+use robo_controller::*;
+
+/// The number of steps between switching direction.
+const STEPS_PER_SWITCH: u32 = 24;
+
+pub struct Spinner {
+    steps: u32,
+    reversing: bool,
+}
+
+impl Spinner {
+    pub fn new() -> Self {
+        Spinner {
+            steps: STEPS_PER_SWITCH,
+            reversing: false,
+        }
+    }
+}
+
+impl RoboController for Spinner {
+    fn init(&mut self, hook: &mut RoboHook) {
+        hook.set_turn_rate(10.0);
+        hook.set_gun_turn_rate(-10.0);
+        hook.set_thrust(10.0);
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, _elapsed: f64) {
+        self.steps -= 1;
+
+        if self.steps == 0 {
+            self.steps = STEPS_PER_SWITCH;
+            self.reversing = !self.reversing;
+
+            if self.reversing {
+                hook.set_thrust(-10.0);
+            } else {
+                hook.set_thrust(10.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Drives anticlockwise round the edge of the arena, keeping its radar
+//! spinning and shooting straight at whatever it sees.
+
+use math::*;
+use robo_controller::*;
+
+use super::*;
+
+use std::f64::consts::PI;
+
+/// How close to the walls it drives.
+const MARGIN: f64 = 3.0;
+
+pub struct WallHugger {
+    /// Seconds since the start of the match.
+    time: f64,
+    tracker: Tracker,
+}
+
+impl WallHugger {
+    pub fn new() -> Self {
+        WallHugger {
+            time: 0.0,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+impl RoboController for WallHugger {
+    fn init(&mut self, hook: &mut RoboHook) {
+        let radar_turn_rate = hook.config().radar_turn_rate_limits.max;
+        hook.set_radar_turn_rate(radar_turn_rate);
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, elapsed: f64) {
+        self.time += elapsed;
+
+        let config = hook.config().clone();
+        let next = next_state(hook);
+        let pos = next.pos;
+        let size = config.world_size;
+
+        // Each wall's distance, the heading along it anticlockwise, and the
+        // heading towards it.
+        let walls = [(pos.y, 0.0, -PI / 2.0),
+                     (size.x - pos.x, PI / 2.0, 0.0),
+                     (size.y - pos.y, PI, PI / 2.0),
+                     (pos.x, -PI / 2.0, PI)];
+
+        let mut nearest = walls[0];
+        for wall in &walls[1..] {
+            if wall.0 < nearest.0 {
+                nearest = *wall;
+            }
+        }
+
+        // Steer along the wall, angled in when further from it than the margin
+        // and out when nearer, so that it comes back after overshooting a
+        // corner.
+        let (distance, along, towards) = nearest;
+        let offset = ((distance - MARGIN) / MARGIN).max(-1.0).min(1.0);
+        let heading = along + angle_between(along, towards) * offset;
+
+        let turn_rate = turn_towards(&config, &next, heading);
+        hook.set_turn_rate(turn_rate);
+        hook.set_thrust(config.thrust_limits.max);
+
+        // With nothing to shoot at, the gun faces into the arena.
+        match self.tracker.target(self.time) {
+            Some(target) => {
+                let fire_time = self.time + config.step_seconds();
+                aim_and_shoot(hook, &next, target.pos_at(fire_time))
+            }
+            None => {
+                let inwards = bearing(pos, centre(&config));
+                let gun_turn_rate = gun_towards(&config, &next, inwards, hook.turn_rate);
+                hook.set_gun_turn_rate(gun_turn_rate);
+            }
+        }
+    }
+
+    fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {
+        self.tracker.scan(hook.pos, scan_pos, self.time);
+    }
+}
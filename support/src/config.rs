@@ -39,6 +39,28 @@ pub struct Config {
 
     /// The range of allowed power settings for bullets.
     pub bullet_power_limits: Clamped<f64>,
+
+    /// The speed of bullets, in units per second.
+    pub bullet_speed: f64,
+
+    /// The radius within which a bullet will hit a robot.
+    pub robot_radius: f64,
+
+    /// How far away robots can be seen by radar. A robot is scanned when it is
+    /// within this range and the arc swept by another robot's radar over a
+    /// tick.
+    pub radar_range: f64,
+
+    /// The number of strikes after which a robot is disqualified. A robot gets
+    /// a strike for each step in which it sends invalid responses. Responding
+    /// late is only counted as an error.
+    pub max_strikes: u32,
+
+    /// Whether to run the simulation deterministically. Robots are ticked as if
+    /// exactly one tick's duration has passed, and each step waits for the
+    /// robot's responses to the previous step instead of giving a strike when
+    /// they are slow. This allows recorded matches to be re-run exactly.
+    pub deterministic: bool,
 }
 
 impl Default for Config {
@@ -60,6 +82,14 @@ impl Default for Config {
             max_shoot_power: 100.0,
             shoot_power_per_tick: 100.0,
             bullet_power_limits: Clamped::new(1.0, 5.0),
+
+            bullet_speed: 50.0,
+            robot_radius: 0.6,
+            radar_range: 40.0,
+
+            max_strikes: 3,
+
+            deterministic: false,
         }
     }
 }
@@ -71,4 +101,19 @@ impl Config {
         let nanos = (exact * 1_000_000_000.0) as u32;
         Duration::new(0, nanos)
     }
+
+    /// The length of a step, `ticks_per_step` ticks, as a `Duration`.
+    pub fn step_duration(&self) -> Duration {
+        self.tick_duration() * self.ticks_per_step
+    }
+
+    /// Calculate the length of a tick in seconds based on the `ticks_per_second`.
+    pub fn tick_seconds(&self) -> f64 {
+        1.0 / self.ticks_per_second as f64
+    }
+
+    /// The length of a step, `ticks_per_step` ticks, in seconds.
+    pub fn step_seconds(&self) -> f64 {
+        self.ticks_per_step as f64 * self.tick_seconds()
+    }
 }
@@ -0,0 +1,513 @@
+//! Delta compression of snapshots, for replays and streaming.
+//!
+//! A stream of snapshots is encoded as a sequence of `Frame`s. A keyframe holds
+//! a whole snapshot, and each delta after it holds only what changed since that
+//! keyframe. Because deltas are relative to the keyframe rather than to the
+//! previous frame, any frame can be decoded from its keyframe alone, which
+//! makes seeking cheap.
+
+use math::*;
+use rpc::*;
+use snapshot::*;
+
+/// The version of the frame schema written by this module. Keyframes with any
+/// other version are rejected when decoding.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The usual number of ticks between keyframes.
+pub const DEFAULT_KEYFRAME_INTERVAL: u64 = 60;
+
+/// An encoded snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Frame {
+    /// A whole snapshot, which later deltas are relative to.
+    Key {
+        version: u32,
+        snapshot: Snapshot,
+    },
+
+    Delta(Delta),
+}
+
+/// The changes in a snapshot since the last keyframe. Bullets and events rarely
+/// last long, so they are always stored in full.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delta {
+    /// The tick of the keyframe this delta is relative to.
+    pub keyframe_tick: u64,
+
+    pub tick: u64,
+
+    /// The robots which have changed, in world order.
+    pub bots: Vec<BotDelta>,
+
+    pub bullets: Vec<Bullet>,
+    pub events: Vec<Event>,
+
+    /// The index and new value of each changed score.
+    pub scores: Vec<(usize, Score)>,
+
+    /// Every debug shape being drawn, unless they are the same as in the
+    /// keyframe. Robots often keep drawing the same shapes for a while.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drawings: Option<Vec<Drawing>>,
+
+    /// The index and new value of each robot's changed diagnostics.
+    pub diagnostics: Vec<(usize, Diagnostics)>,
+}
+
+/// The fields of a robot's state which have changed since the keyframe. Fields
+/// which haven't changed are `None`, and are left out when serialised.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BotDelta {
+    /// The robot's index in the world.
+    pub index: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<Vector2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gun_heading: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub radar_heading: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thrust: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gun_turn_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub radar_turn_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hit_points: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shoot_power: Option<f64>,
+
+    /// The colour the robot changed to. A robot can't go back to having no
+    /// colour in a delta; that needs a keyframe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnsupportedVersion(u32),
+
+    /// A delta arrived without its keyframe. Holds the tick of the keyframe
+    /// it needs.
+    MissingKeyframe(u64),
+
+    /// A delta refers to a robot, score or diagnostics which isn't in its
+    /// keyframe.
+    BadIndex(usize),
+}
+
+/// Turns snapshots into frames, writing a keyframe at least every
+/// `keyframe_interval` ticks.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    keyframe_interval: u64,
+    keyframe: Option<Snapshot>,
+}
+
+/// Turns frames back into snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct Decoder {
+    keyframe: Option<Snapshot>,
+}
+
+/// Set each field of a `BotDelta` which differs between two robots' states.
+macro_rules! diff_fields {
+    ($delta:expr, $from:expr, $to:expr, $($field:ident),*) => {
+        $(
+            if $from.$field != $to.$field {
+                $delta.$field = Some($to.$field);
+            }
+        )*
+    }
+}
+
+/// Apply each field that is set in a `BotDelta` to a robot's state.
+macro_rules! apply_fields {
+    ($delta:expr, $bot:expr, $($field:ident),*) => {
+        $(
+            if let Some(value) = $delta.$field {
+                $bot.$field = value;
+            }
+        )*
+    }
+}
+
+impl Encoder {
+    pub fn new(keyframe_interval: u64) -> Self {
+        Encoder {
+            keyframe_interval: keyframe_interval.max(1),
+            keyframe: None,
+        }
+    }
+
+    /// Encode the next snapshot. A keyframe is written when the interval is
+    /// up, when going back in time, or when the change can't be expressed as a
+    /// delta.
+    pub fn encode(&mut self, snapshot: &Snapshot) -> Frame {
+        let delta = match self.keyframe {
+            Some(ref keyframe) if snapshot.tick >= keyframe.tick &&
+                                  snapshot.tick - keyframe.tick < self.keyframe_interval => {
+                delta(keyframe, snapshot)
+            }
+            _ => None,
+        };
+
+        match delta {
+            Some(delta) => Frame::Delta(delta),
+            None => {
+                self.keyframe = Some(snapshot.clone());
+
+                Frame::Key {
+                    version: SCHEMA_VERSION,
+                    snapshot: snapshot.clone(),
+                }
+            }
+        }
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Encoder::new(DEFAULT_KEYFRAME_INTERVAL)
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder::default()
+    }
+
+    /// Decode the next frame into a whole snapshot.
+    pub fn decode(&mut self, frame: Frame) -> Result<Snapshot, Error> {
+        match frame {
+            Frame::Key { version, snapshot } => {
+                if version != SCHEMA_VERSION {
+                    return Err(Error::UnsupportedVersion(version));
+                }
+
+                self.keyframe = Some(snapshot.clone());
+                Ok(snapshot)
+            }
+
+            Frame::Delta(delta) => {
+                match self.keyframe {
+                    Some(ref keyframe) if keyframe.tick == delta.keyframe_tick => {
+                        apply(keyframe, delta)
+                    }
+                    _ => Err(Error::MissingKeyframe(delta.keyframe_tick)),
+                }
+            }
+        }
+    }
+}
+
+/// The changes from a keyframe to a snapshot, if they can be expressed as a
+/// delta.
+fn delta(keyframe: &Snapshot, snapshot: &Snapshot) -> Option<Delta> {
+    if keyframe.bots.len() != snapshot.bots.len() ||
+       keyframe.scores.len() != snapshot.scores.len() ||
+       keyframe.diagnostics.len() != snapshot.diagnostics.len() {
+        return None;
+    }
+
+    let mut bots = Vec::new();
+
+    for (index, (from, to)) in keyframe.bots.iter().zip(&snapshot.bots).enumerate() {
+        if from == to {
+            continue;
+        }
+
+        let mut bot = BotDelta { index: index, ..BotDelta::default() };
+
+        diff_fields!(bot,
+                     from,
+                     to,
+                     pos,
+                     heading,
+                     gun_heading,
+                     radar_heading,
+                     speed,
+                     thrust,
+                     turn_rate,
+                     gun_turn_rate,
+                     radar_turn_rate,
+                     hit_points,
+                     shoot_power);
+
+        if from.color != to.color {
+            match to.color {
+                Some(color) => bot.color = Some(color),
+                None => return None,
+            }
+        }
+
+        bots.push(bot);
+    }
+
+    Some(Delta {
+        keyframe_tick: keyframe.tick,
+        tick: snapshot.tick,
+        bots: bots,
+        bullets: snapshot.bullets.clone(),
+        events: snapshot.events.clone(),
+        scores: changed(&keyframe.scores, &snapshot.scores),
+        drawings: if snapshot.drawings == keyframe.drawings {
+            None
+        } else {
+            Some(snapshot.drawings.clone())
+        },
+        diagnostics: changed(&keyframe.diagnostics, &snapshot.diagnostics),
+    })
+}
+
+/// Rebuild a snapshot from its keyframe and delta.
+fn apply(keyframe: &Snapshot, delta: Delta) -> Result<Snapshot, Error> {
+    let mut bots = keyframe.bots.clone();
+
+    for change in delta.bots {
+        let bot: &mut BotState = try!(bots.get_mut(change.index)
+            .ok_or(Error::BadIndex(change.index)));
+
+        apply_fields!(change,
+                      bot,
+                      pos,
+                      heading,
+                      gun_heading,
+                      radar_heading,
+                      speed,
+                      thrust,
+                      turn_rate,
+                      gun_turn_rate,
+                      radar_turn_rate,
+                      hit_points,
+                      shoot_power);
+
+        if let Some(color) = change.color {
+            bot.color = Some(color);
+        }
+    }
+
+    Ok(Snapshot {
+        tick: delta.tick,
+        bots: bots,
+        bullets: delta.bullets,
+        events: delta.events,
+        scores: try!(replace_changed(&keyframe.scores, delta.scores)),
+        drawings: delta.drawings.unwrap_or_else(|| keyframe.drawings.clone()),
+        diagnostics: try!(replace_changed(&keyframe.diagnostics, delta.diagnostics)),
+    })
+}
+
+/// The index and new value of each item which differs between two lists of
+/// the same length.
+fn changed<T: Clone + PartialEq>(from: &[T], to: &[T]) -> Vec<(usize, T)> {
+    from.iter()
+        .zip(to)
+        .enumerate()
+        .filter(|&(_, (a, b))| a != b)
+        .map(|(i, (_, b))| (i, b.clone()))
+        .collect()
+}
+
+/// A copy of the list with the given items replaced.
+fn replace_changed<T: Clone>(from: &[T], changes: Vec<(usize, T)>) -> Result<Vec<T>, Error> {
+    let mut items = from.to_vec();
+
+    for (index, value) in changes {
+        match items.get_mut(index) {
+            Some(item) => *item = value,
+            None => return Err(Error::BadIndex(index)),
+        }
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::*;
+    use rpc::*;
+    use snapshot::*;
+
+    use serde_json;
+
+    /// A small deterministic random number generator, so that failures can be
+    /// reproduced.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A number between 0 and 1.
+        fn float(&mut self) -> f64 {
+            (self.next() % 1_000_000) as f64 / 1_000_000.0
+        }
+
+        /// True with the given probability.
+        fn chance(&mut self, p: f64) -> bool {
+            self.float() < p
+        }
+    }
+
+    fn random_bot(rng: &mut XorShift) -> BotState {
+        BotState {
+            pos: Vector2::new(rng.float() * 100.0, rng.float() * 100.0),
+            heading: rng.float() * 6.0,
+            gun_heading: rng.float() * 6.0,
+            radar_heading: rng.float() * 6.0,
+            speed: rng.float(),
+            thrust: rng.float(),
+            turn_rate: rng.float(),
+            gun_turn_rate: rng.float(),
+            radar_turn_rate: rng.float(),
+            hit_points: rng.float() * 100.0,
+            shoot_power: rng.float() * 100.0,
+            color: if rng.chance(0.5) { Some([0.1, 0.2, 0.3]) } else { None },
+        }
+    }
+
+    /// Change some of each robot's fields, and the rest of the snapshot, at
+    /// random.
+    fn mutate(rng: &mut XorShift, snapshot: &Snapshot) -> Snapshot {
+        let mut next = snapshot.clone();
+        next.tick += 1 + rng.next() % 3;
+
+        for bot in &mut next.bots {
+            if rng.chance(0.5) {
+                bot.pos += Vector2::new(rng.float(), rng.float());
+            }
+            if rng.chance(0.3) {
+                bot.heading += rng.float();
+            }
+            if rng.chance(0.3) {
+                bot.radar_heading += rng.float();
+            }
+            if rng.chance(0.1) {
+                bot.hit_points -= rng.float();
+            }
+            if rng.chance(0.05) {
+                bot.color = if rng.chance(0.5) {
+                    Some([rng.float() as f32, 0.5, 0.5])
+                } else {
+                    None
+                };
+            }
+        }
+
+        next.bullets = (0..rng.next() % 4)
+            .map(|i| {
+                Bullet {
+                    owner: i as usize,
+                    pos: Vector2::new(rng.float(), rng.float()),
+                    vel: Vector2::new(rng.float(), rng.float()),
+                    power: rng.float(),
+                }
+            })
+            .collect();
+
+        next.events = if rng.chance(0.3) {
+            vec![Event::Death { bot: (rng.next() % 5) as usize }]
+        } else {
+            Vec::new()
+        };
+
+        // Drawings often stay the same for a while, which deltas leave out.
+        if rng.chance(0.2) {
+            next.drawings = if rng.chance(0.5) {
+                vec![Drawing {
+                         bot: 0,
+                         shape: DebugShape::Point { pos: Vector2::new(rng.float(), rng.float()) },
+                         color: [0.5, 0.5, 0.5],
+                     }]
+            } else {
+                Vec::new()
+            };
+        }
+
+        for score in &mut next.scores {
+            if rng.chance(0.1) {
+                score.kills += 1;
+            }
+        }
+
+        for diagnostics in &mut next.diagnostics {
+            if rng.chance(0.1) {
+                diagnostics.latency = rng.float();
+            }
+        }
+
+        // Occasionally go back in time, as when seeking.
+        if rng.chance(0.02) {
+            next.tick = rng.next() % (next.tick + 1);
+        }
+
+        next
+    }
+
+    fn round_trip_json<T: ::serde::Serialize + ::serde::de::DeserializeOwned>(value: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for seed in 1..20 {
+            let mut rng = XorShift(seed * 0x9e3779b97f4a7c15);
+            let mut encoder = Encoder::new(1 + rng.next() % 20);
+            let mut decoder = Decoder::new();
+
+            let num_bots = (rng.next() % 8) as usize;
+            let mut snapshot = Snapshot {
+                bots: (0..num_bots).map(|_| random_bot(&mut rng)).collect(),
+                scores: vec![Score::default(); num_bots],
+                diagnostics: vec![Diagnostics::default(); num_bots],
+                ..Snapshot::default()
+            };
+
+            for _ in 0..200 {
+                // Frames go through the same serialisation as replay files, so
+                // they are compared with the snapshot serialised in full.
+                let frame = encoder.encode(&snapshot);
+                let frame: Frame = round_trip_json(&frame);
+
+                assert_eq!(decoder.decode(frame), Ok(round_trip_json(&snapshot)));
+
+                snapshot = mutate(&mut rng, &snapshot);
+            }
+        }
+    }
+
+    #[test]
+    fn delta_without_keyframe() {
+        let mut encoder = Encoder::new(10);
+        encoder.encode(&Snapshot::default());
+
+        let frame = encoder.encode(&Snapshot { tick: 1, ..Snapshot::default() });
+        assert_eq!(Decoder::new().decode(frame), Err(Error::MissingKeyframe(0)));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let frame = Frame::Key {
+            version: SCHEMA_VERSION + 1,
+            snapshot: Snapshot::default(),
+        };
+
+        assert_eq!(Decoder::new().decode(frame),
+                   Err(Error::UnsupportedVersion(SCHEMA_VERSION + 1)));
+    }
+}
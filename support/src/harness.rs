@@ -0,0 +1,474 @@
+//! A scripted mini-world for unit testing a `RoboController` without the
+//! simulator, or any processes. The robot is driven tick by tick with the same
+//! physics and timing as in a deterministic match: it is sent a step every
+//! `ticks_per_step` ticks, and whatever it responds with takes effect at the
+//! step after. Scans and hits are fed in by the test. Responses are checked and
+//! applied by the same rules as in the simulator.
+//!
+//! ```ignore
+//! let mut harness = Harness::new(MyBot::new(), Config::default(), Vector2::new(50.0, 50.0));
+//! harness.scan(Vector2::new(60.0, 50.0));
+//! let resps = harness.run_steps(2);
+//! assert!(resps.iter().any(|resp| match *resp { Response::Shoot(_) => true, _ => false }));
+//! ```
+
+use config::Config;
+use math::*;
+use physics;
+use responses::{self, DebugDrawings};
+use robo_controller::{Host, RoboController};
+use rpc::*;
+use snapshot::Bullet;
+
+use std::mem;
+
+pub use responses::Error;
+
+/// A single robot in an otherwise empty world.
+pub struct Harness<C> {
+    host: Host<C>,
+    config: Config,
+    state: BotState,
+    alive: bool,
+
+    ticks: u64,
+    ticks_until_step: u32,
+    elapsed_since_step: f64,
+
+    /// The responses to each message sent since the last step, which take
+    /// effect at the next.
+    pending: Vec<Vec<Response>>,
+    next_shot_power: Option<f64>,
+    drawings: DebugDrawings,
+
+    bullets: Vec<Bullet>,
+    errors: Vec<Error>,
+}
+
+impl<C: RoboController> Harness<C> {
+    /// Create a robot at the given position and initialise its controller.
+    pub fn new(controller: C, config: Config, initial_pos: Vector2) -> Self {
+        let mut harness = Harness {
+            host: Host::new(controller),
+            ticks_until_step: config.ticks_per_step,
+            state: BotState {
+                pos: initial_pos,
+                hit_points: config.max_hit_points,
+                shoot_power: config.max_shoot_power,
+                ..BotState::default()
+            },
+            config: config.clone(),
+            alive: true,
+            ticks: 0,
+            elapsed_since_step: 0.0,
+            pending: Vec::new(),
+            next_shot_power: None,
+            drawings: DebugDrawings::new(),
+            bullets: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        harness.send(Message::Init { config: config });
+        harness
+    }
+
+    /// The robot's state, as the simulator would see it.
+    #[inline]
+    pub fn state(&self) -> &BotState { &self.state }
+
+    /// Change the robot's state directly, say to put it somewhere in
+    /// particular.
+    #[inline]
+    pub fn state_mut(&mut self) -> &mut BotState { &mut self.state }
+
+    #[inline]
+    pub fn config(&self) -> &Config { &self.config }
+
+    #[inline]
+    pub fn controller(&self) -> &C { self.host.controller() }
+
+    #[inline]
+    pub fn controller_mut(&mut self) -> &mut C { self.host.controller_mut() }
+
+    /// The number of ticks run so far.
+    #[inline]
+    pub fn ticks(&self) -> u64 { self.ticks }
+
+    #[inline]
+    pub fn is_alive(&self) -> bool { self.alive }
+
+    /// The invalid responses the robot has sent so far, in order.
+    #[inline]
+    pub fn errors(&self) -> &[Error] { &self.errors }
+
+    /// The debug shapes the robot currently wants drawn.
+    #[inline]
+    pub fn debug_drawings(&self) -> &[DebugDrawing] { self.drawings.drawings() }
+
+    /// How many debug shapes the simulator would have dropped, for being
+    /// invalid or one too many.
+    #[inline]
+    pub fn dropped_drawings(&self) -> u32 { self.drawings.dropped() }
+
+    /// Take the bullets fired since this was last called.
+    pub fn take_bullets(&mut self) -> Vec<Bullet> {
+        mem::replace(&mut self.bullets, Vec::new())
+    }
+
+    /// Run a single tick, returning the responses the robot sent in it, if it
+    /// was stepped.
+    pub fn tick(&mut self) -> Vec<Response> {
+        if !self.alive {
+            return Vec::new();
+        }
+
+        // Ticks in a deterministic match are `tick_duration` long.
+        let elapsed = physics::duration_float(self.config.tick_duration());
+        let mut resps = Vec::new();
+
+        self.ticks += 1;
+        self.ticks_until_step -= 1;
+        self.elapsed_since_step += elapsed;
+
+        if self.ticks_until_step == 0 {
+            self.ticks_until_step = self.config.ticks_per_step;
+
+            self.drawings.end_step();
+
+            // Each message's responses are applied on their own, and a shot
+            // asked for in answer to a later message replaces an earlier one.
+            for resps in mem::replace(&mut self.pending, Vec::new()) {
+                let mut shot = None;
+
+                for resp in resps {
+                    let result = responses::apply(&self.config,
+                                                  &mut self.state,
+                                                  &mut self.drawings,
+                                                  &mut shot,
+                                                  resp);
+                    if let Err(err) = result {
+                        self.errors.push(err);
+                    }
+                }
+
+                if shot.is_some() {
+                    self.next_shot_power = shot;
+                }
+            }
+
+            let elapsed_since_step = self.elapsed_since_step;
+            resps = self.send(Message::Step { elapsed: elapsed_since_step });
+            self.elapsed_since_step = 0.0;
+        }
+
+        physics::advance(&self.config, &mut self.state, elapsed);
+        self.drawings.advance(elapsed);
+
+        let power = self.next_shot_power.take();
+        if let Some(bullet) = physics::shoot(&self.config, &mut self.state, power) {
+            self.bullets.push(bullet);
+        }
+
+        resps
+    }
+
+    /// Run the given number of ticks, returning every response sent in them.
+    pub fn run_ticks(&mut self, ticks: u32) -> Vec<Response> {
+        let mut resps = Vec::new();
+
+        for _ in 0..ticks {
+            resps.extend(self.tick());
+        }
+
+        resps
+    }
+
+    /// Run up to and including the next step, returning the responses to it.
+    pub fn step(&mut self) -> Vec<Response> {
+        let ticks = self.ticks_until_step;
+        self.run_ticks(ticks)
+    }
+
+    /// Run the given number of steps, returning every response sent in them.
+    pub fn run_steps(&mut self, steps: u32) -> Vec<Response> {
+        let mut resps = Vec::new();
+
+        for _ in 0..steps {
+            resps.extend(self.step());
+        }
+
+        resps
+    }
+
+    /// Tell the robot its radar swept over another robot at the given
+    /// position, returning its responses. They take effect at the next step.
+    pub fn scan(&mut self, scan_pos: Vector2) -> Vec<Response> {
+        if !self.alive {
+            return Vec::new();
+        }
+
+        self.send(Message::Scan { scan_pos: scan_pos })
+    }
+
+    /// Hit the robot with a bullet doing the given damage, killing it if it
+    /// has no hit points left.
+    pub fn hit(&mut self, damage: f64) {
+        if !self.alive {
+            return;
+        }
+
+        self.state.hit_points -= damage;
+
+        if self.state.hit_points <= 0.0 {
+            self.state.hit_points = 0.0;
+            self.kill();
+        }
+    }
+
+    /// End the robot's match, as if the simulation were over.
+    pub fn kill(&mut self) {
+        if self.alive {
+            self.alive = false;
+            self.drawings.clear();
+            self.host.respond(self.state.clone(), Message::Kill);
+        }
+    }
+
+    /// Send a message to the robot, queueing its responses for the next step.
+    fn send(&mut self, msg: Message) -> Vec<Response> {
+        let resps = self.host.respond(self.state.clone(), msg);
+        self.pending.push(resps.clone());
+        resps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bots::circular_gunner::CircularGunner;
+    use bots::duelist::Duelist;
+    use bots::linear_gunner::LinearGunner;
+    use bots::random_walker::RandomWalker;
+    use bots::sitting_duck::SittingDuck;
+    use bots::spinner::Spinner;
+    use bots::wall_hugger::WallHugger;
+    use config::Config;
+    use math::*;
+    use responses::MAX_DEBUG_DRAWINGS;
+    use robo_controller::{RoboController, RoboHook};
+    use rpc::*;
+
+    fn middle(config: &Config) -> Vector2 {
+        config.world_size * 0.5
+    }
+
+    fn shots(resps: &[Response]) -> usize {
+        resps.iter()
+            .filter(|resp| match **resp {
+                Response::Shoot(_) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    /// How close a robot comes to the nearest wall.
+    fn wall_distance(config: &Config, pos: Vector2) -> f64 {
+        pos.x.min(pos.y).min(config.world_size.x - pos.x).min(config.world_size.y - pos.y)
+    }
+
+    /// Where a robot driving anticlockwise round a circle near the middle is,
+    /// a number of seconds into the match.
+    fn circling(config: &Config, time: f64) -> Vector2 {
+        let (sin, cos) = time.sin_cos();
+        middle(config) + Vector2::new(15.0 + 8.0 * cos, 8.0 * sin)
+    }
+
+    #[test]
+    fn sitting_duck_stays_put() {
+        let config = Config::default();
+        let mut harness = Harness::new(SittingDuck, config.clone(), middle(&config));
+
+        assert!(harness.run_steps(10).is_empty());
+        assert_eq!(harness.state().pos, middle(&config));
+        assert_eq!(harness.ticks(), 10 * config.ticks_per_step as u64);
+    }
+
+    #[test]
+    fn responses_take_effect_at_the_next_step() {
+        let config = Config::default();
+        let mut harness = Harness::new(Spinner::new(), config.clone(), middle(&config));
+
+        // The responses to `init` are applied at the first step, and clamped
+        // to the limits by the hook.
+        harness.run_ticks(config.ticks_per_step - 1);
+        assert_eq!(harness.state().thrust, 0.0);
+
+        harness.tick();
+        assert_eq!(harness.state().thrust, config.thrust_limits.max);
+        assert_eq!(harness.state().turn_rate, config.turn_rate_limits.max);
+        assert!(harness.state().pos != middle(&config));
+        assert!(harness.errors().is_empty());
+    }
+
+    #[test]
+    fn linear_gunner_shoots_at_scans() {
+        let config = Config::default();
+        let pos = middle(&config);
+        let mut harness = Harness::new(LinearGunner::new(), config.clone(), pos);
+
+        let mut shots_fired = 0;
+        for _ in 0..20 {
+            harness.scan(pos + Vector2::new(10.0, 0.0));
+            shots_fired += shots(&harness.step());
+        }
+
+        assert!(shots_fired > 0);
+        assert!(harness.errors().is_empty());
+        assert!(!harness.take_bullets().is_empty());
+    }
+
+    #[test]
+    fn wall_hugger_keeps_to_the_walls() {
+        let config = Config::default();
+        let mut harness = Harness::new(WallHugger::new(), config.clone(), middle(&config));
+
+        // Give it time to drive out to a wall, then to go round every corner.
+        harness.run_steps(250);
+        for _ in 0..600 {
+            harness.step();
+            let distance = wall_distance(&config, harness.state().pos);
+            assert!(distance > 0.0 && distance < 5.0);
+        }
+
+        assert!(harness.errors().is_empty());
+    }
+
+    #[test]
+    fn random_walker_is_deterministic() {
+        let config = Config::default();
+        let pos = middle(&config);
+        let mut first = Harness::new(RandomWalker::new(), config.clone(), pos);
+        let mut second = Harness::new(RandomWalker::new(), config.clone(), pos);
+
+        // Its random numbers are seeded by where it starts.
+        let other_pos = pos + Vector2::new(1.0, 0.0);
+        let mut other = Harness::new(RandomWalker::new(), config.clone(), other_pos);
+
+        for _ in 0..100 {
+            assert_eq!(first.step(), second.step());
+            other.step();
+        }
+
+        assert_eq!(first.state(), second.state());
+        assert!(first.state().pos - pos != other.state().pos - other_pos);
+    }
+
+    #[test]
+    fn circular_gunner_hits_circling_robots() {
+        let config = Config::default();
+        let mut harness = Harness::new(CircularGunner::new(), config.clone(), middle(&config));
+        let tick_seconds = config.tick_seconds();
+
+        let mut bullets = Vec::new();
+        for _ in 0..100 {
+            let time = harness.ticks() as f64 * tick_seconds;
+            harness.scan(circling(&config, time));
+            harness.step();
+
+            let fired = harness.ticks();
+            bullets.extend(harness.take_bullets().into_iter().map(|bullet| (fired, bullet)));
+        }
+
+        // Follow each bullet out to beyond the target's circle.
+        let hits = bullets.iter()
+            .filter(|&&(fired, ref bullet)| {
+                (0..60).any(|ticks| {
+                    let time = (fired + ticks) as f64 * tick_seconds;
+                    let pos = bullet.pos + bullet.vel * (ticks as f64 * tick_seconds);
+                    (pos - circling(&config, time)).length() <= config.robot_radius
+                })
+            })
+            .count();
+
+        assert!(!bullets.is_empty());
+        assert!(hits * 2 >= bullets.len());
+        assert!(harness.errors().is_empty());
+    }
+
+    #[test]
+    fn duelist_strafes_and_shoots_its_target() {
+        let config = Config::default();
+        let target = middle(&config) + Vector2::new(20.0, 0.0);
+        let mut harness = Harness::new(Duelist::new(), config.clone(), middle(&config));
+
+        let mut shots_fired = 0;
+        for step in 0..200 {
+            harness.scan(target);
+            shots_fired += shots(&harness.step());
+
+            // Once it has closed in, it keeps near its preferred distance.
+            if step >= 100 {
+                let distance = (harness.state().pos - target).length();
+                assert!(distance > 5.0 && distance < 25.0);
+            }
+        }
+
+        assert!(shots_fired > 0);
+        assert!(harness.errors().is_empty());
+    }
+
+    #[test]
+    fn hits_kill() {
+        let config = Config::default();
+        let mut harness = Harness::new(SittingDuck, config.clone(), middle(&config));
+
+        harness.hit(config.max_hit_points / 2.0);
+        assert!(harness.is_alive());
+
+        harness.hit(config.max_hit_points);
+        assert!(!harness.is_alive());
+        assert_eq!(harness.state().hit_points, 0.0);
+        assert!(harness.step().is_empty());
+    }
+
+    struct DoubleShooter;
+
+    impl RoboController for DoubleShooter {
+        fn step(&mut self, hook: &mut RoboHook, _elapsed: f64) {
+            hook.shoot(1.0);
+            hook.shoot(1.0);
+        }
+    }
+
+    #[test]
+    fn invalid_responses_are_errors() {
+        let config = Config::default();
+        let mut harness = Harness::new(DoubleShooter, config.clone(), middle(&config));
+
+        harness.run_steps(2);
+        assert_eq!(harness.errors(), &[Error::TooManyBulletsPerFrame]);
+    }
+
+    struct Scribbler;
+
+    impl RoboController for Scribbler {
+        fn step(&mut self, hook: &mut RoboHook, _elapsed: f64) {
+            let pos = hook.pos;
+            for _ in 0..MAX_DEBUG_DRAWINGS + 1 {
+                hook.draw_point(pos, [1.0, 0.0, 0.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn excess_drawings_are_dropped() {
+        let config = Config::default();
+        let mut harness = Harness::new(Scribbler, config.clone(), middle(&config));
+
+        // Each step's shapes replace the last's, rather than adding to them.
+        harness.run_steps(3);
+        assert_eq!(harness.debug_drawings().len(), MAX_DEBUG_DRAWINGS);
+        assert_eq!(harness.dropped_drawings(), 2);
+        assert!(harness.errors().is_empty());
+    }
+}
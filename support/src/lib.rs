@@ -1,14 +1,22 @@
-#![feature(custom_derive, plugin)]
-#![plugin(serde_macros)]
-
 extern crate cgmath;
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
 
 pub mod math;
 pub mod config;
 pub mod rpc;
 pub mod robo_controller;
+pub mod actions;
+pub mod snapshot;
+pub mod replay;
+pub mod delta;
+pub mod bots;
+pub mod physics;
+pub mod responses;
+pub mod harness;
+pub mod targeting;
 
 #[cfg(test)]
 mod tests {
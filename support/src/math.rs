@@ -34,6 +34,12 @@ pub mod vector {
         pub fn into_cgmath(self) -> cgmath::Vector2<f64> {
             cgmath::Vector2::new(self.x, self.y)
         }
+
+        /// Returns the length (magnitude) of the vector.
+        #[inline]
+        pub fn length(&self) -> f64 {
+            self.x.hypot(self.y)
+        }
     }
 
     impl<'a, 'b> Add<&'a Vector2> for &'b Vector2 {
@@ -0,0 +1,53 @@
+//! The rules for how a robot moves and shoots each tick, shared by the
+//! simulator and the local test harness so that they can't drift apart.
+
+use config::Config;
+use math::*;
+use rpc::BotState;
+use snapshot::Bullet;
+
+use std::time::Duration;
+
+/// The length of a duration in seconds. The world and every robot convert
+/// each tick's duration with this, so that bullets and robots are moved by
+/// exactly the same time.
+pub fn duration_float(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+/// Move a robot on by a tick of the given length in seconds, turning its body,
+/// gun and radar, and accelerating it by its thrust.
+pub fn advance(config: &Config, state: &mut BotState, elapsed: f64) {
+    state.heading += state.turn_rate * elapsed;
+    state.gun_heading += (state.gun_turn_rate + state.turn_rate) * elapsed;
+    state.radar_heading += (state.radar_turn_rate + state.turn_rate) * elapsed;
+    state.speed += state.thrust * elapsed;
+    state.speed *= config.drive_friction;
+
+    let dir = Vector2::new(state.heading.cos(), state.heading.sin());
+    state.pos += dir * state.speed * elapsed;
+}
+
+/// Recharge a robot's shoot power for a tick, then fire a shot of the given
+/// power if one was asked for. A shot is silently dropped if there isn't
+/// enough power for it. The bullet's `owner` is left for the world to fill in.
+pub fn shoot(config: &Config, state: &mut BotState, power: Option<f64>) -> Option<Bullet> {
+    state.shoot_power = (state.shoot_power + config.shoot_power_per_tick)
+        .min(config.max_shoot_power);
+
+    let power = match power {
+        Some(power) if power <= state.shoot_power => power,
+        _ => return None,
+    };
+
+    state.shoot_power -= power;
+
+    let gun_dir = Vector2::new(state.gun_heading.cos(), state.gun_heading.sin());
+
+    Some(Bullet {
+        owner: 0,
+        pos: state.pos,
+        vel: gun_dir * config.bullet_speed,
+        power: power,
+    })
+}
@@ -0,0 +1,168 @@
+//! Reading and writing recorded matches.
+//!
+//! A replay file is a sequence of lines, each holding a single JSON value. The
+//! first line is a `Header` describing the match, and every following line is
+//! the delta-compressed `Frame` of one tick, in order. See the `delta` module.
+
+use config::*;
+use snapshot::*;
+use delta::{self, Encoder, Decoder, Frame};
+
+use serde_json;
+use serde_json::error::Error as SerdeError;
+
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// The version of the replay format written by this crate. Files with any
+/// other version are rejected when reading.
+pub const VERSION: u32 = 8;
+
+/// Information about a robot taking part in a match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BotInfo {
+    /// The id the robot's controller was given.
+    pub id: u64,
+
+    /// The program the robot was run from.
+    pub program: String,
+
+    /// The team the robot is on, if the match is played in teams.
+    pub team: Option<u32>,
+}
+
+impl BotInfo {
+    /// A short name for the robot, taken from its program's file name.
+    pub fn name(&self) -> &str {
+        Path::new(&self.program)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(&self.program)
+    }
+}
+
+/// Everything needed to describe a match, written at the start of the replay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    /// The replay format version. See `VERSION`.
+    pub version: u32,
+
+    /// The world's configuration.
+    pub config: Config,
+
+    /// The seed used for all randomness in setting up the match.
+    pub seed: u64,
+
+    /// The robots taking part, in world order.
+    pub bots: Vec<BotInfo>,
+}
+
+/// An error that can occur while reading or writing a replay.
+#[derive(Debug)]
+pub enum Error {
+    Serialization(SerdeError),
+    Deserialization(SerdeError),
+    Write(io::Error),
+    Read(io::Error),
+    MissingHeader,
+    UnsupportedVersion(u32),
+    Delta(delta::Error),
+}
+
+/// Writes a replay to an underlying writer. Wrap the writer in a
+/// `BufWriter` to avoid a system call per tick.
+pub struct Writer<W: Write> {
+    inner: W,
+    encoder: Encoder,
+}
+
+/// Reads a replay from an underlying reader. Iterating over the reader yields
+/// each tick's snapshot in order.
+pub struct Reader<R: BufRead> {
+    inner: R,
+    header: Header,
+    decoder: Decoder,
+}
+
+impl<W: Write> Writer<W> {
+    /// Start a new replay, writing the header straight away.
+    pub fn new(mut inner: W, header: &Header) -> Result<Self, Error> {
+        try!(write_line(&mut inner, header));
+
+        Ok(Writer {
+            inner: inner,
+            encoder: Encoder::default(),
+        })
+    }
+
+    /// Write the snapshot for the next tick.
+    pub fn write(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+        let frame = self.encoder.encode(snapshot);
+        write_line(&mut self.inner, &frame)
+    }
+
+    /// Flush any buffered output to the underlying writer.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush().map_err(Error::Write)
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Start reading a replay, reading and checking the header straight away.
+    pub fn new(mut inner: R) -> Result<Self, Error> {
+        let header: Header = match try!(read_line(&mut inner)) {
+            Some(header) => header,
+            None => return Err(Error::MissingHeader),
+        };
+
+        if header.version != VERSION {
+            return Err(Error::UnsupportedVersion(header.version));
+        }
+
+        Ok(Reader {
+            inner: inner,
+            header: header,
+            decoder: Decoder::new(),
+        })
+    }
+
+    /// The header describing the recorded match.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Snapshot, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match read_line::<_, Frame>(&mut self.inner) {
+            Ok(Some(frame)) => Some(self.decoder.decode(frame).map_err(Error::Delta)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+fn write_line<W, T>(writer: &mut W, value: &T) -> Result<(), Error>
+    where W: Write,
+          T: ::serde::Serialize
+{
+    let ser = try!(serde_json::to_string(value).map_err(Error::Serialization));
+    writeln!(writer, "{}", ser).map_err(Error::Write)
+}
+
+/// Read a single value from the next line, or return `None` at the end of the
+/// input.
+fn read_line<R, T>(reader: &mut R) -> Result<Option<T>, Error>
+    where R: BufRead,
+          T: ::serde::de::DeserializeOwned
+{
+    let mut buf = String::new();
+    if try!(reader.read_line(&mut buf).map_err(Error::Read)) == 0 {
+        return Ok(None);
+    }
+
+    serde_json::from_str(buf.as_str()).map(Some).map_err(Error::Deserialization)
+}
@@ -0,0 +1,147 @@
+//! How a robot's responses take effect, and which of them are invalid, shared
+//! by the simulator and the local test harness like `physics`.
+
+use config::Config;
+use rpc::*;
+
+/// The most debug shapes a robot can have drawn at once.
+pub const MAX_DEBUG_DRAWINGS: usize = 256;
+
+/// A response the simulator won't accept. Each one counts as an error, and a
+/// step with any earns the robot a strike.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    BadThrust(f64),
+    BadTurnRate(f64),
+    BadGunTurnRate(f64),
+    BadRadarTurnRate(f64),
+    BadBulletPower(f64),
+    BadColor([f32; 3]),
+    TooManyBulletsPerFrame,
+}
+
+/// The debug shapes a robot currently wants drawn. Durations count down to
+/// zero as time passes.
+#[derive(Debug, Clone, Default)]
+pub struct DebugDrawings {
+    drawings: Vec<DebugDrawing>,
+    dropped: u32,
+}
+
+impl DebugDrawings {
+    pub fn new() -> Self {
+        DebugDrawings::default()
+    }
+
+    #[inline]
+    pub fn drawings(&self) -> &[DebugDrawing] { &self.drawings }
+
+    /// How many shapes have been dropped for being invalid, or for being one
+    /// too many.
+    #[inline]
+    pub fn dropped(&self) -> u32 { self.dropped }
+
+    /// Draw a shape. Drawings don't affect the match, so bad ones are only
+    /// dropped rather than being errors.
+    pub fn add(&mut self, drawing: DebugDrawing) {
+        if valid_drawing(&drawing) && self.drawings.len() < MAX_DEBUG_DRAWINGS {
+            self.drawings.push(drawing);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    /// Remove the shapes without a duration, which only last until the robot
+    /// has responded to its next step.
+    pub fn end_step(&mut self) {
+        self.drawings.retain(|drawing| drawing.duration.is_some());
+    }
+
+    /// Count the shapes' durations down by a tick of the given length in
+    /// seconds, removing those which have run out.
+    pub fn advance(&mut self, elapsed: f64) {
+        for drawing in &mut self.drawings {
+            if let Some(ref mut remaining) = drawing.duration {
+                *remaining -= elapsed;
+            }
+        }
+
+        self.drawings.retain(|drawing| drawing.duration.map_or(true, |d| d > 0.0));
+    }
+
+    /// Remove every shape, say because the robot has died.
+    pub fn clear(&mut self) {
+        self.drawings.clear();
+    }
+}
+
+/// Perform the effects of a single response on a robot's state and drawings.
+/// `shot` is the power of the shot asked for so far in the same list of
+/// responses. Debug prints are left to the caller.
+pub fn apply(config: &Config,
+             state: &mut BotState,
+             drawings: &mut DebugDrawings,
+             shot: &mut Option<f64>,
+             resp: Response)
+             -> Result<(), Error> {
+    use rpc::Response::*;
+    use self::Error::*;
+
+    match resp {
+        SetThrust(x) => state.thrust = try!(config.thrust_limits.check(x).map_err(BadThrust)),
+
+        SetTurnRate(x) => {
+            state.turn_rate = try!(config.turn_rate_limits.check(x).map_err(BadTurnRate))
+        }
+
+        SetGunTurnRate(x) => {
+            state.gun_turn_rate =
+                try!(config.gun_turn_rate_limits.check(x).map_err(BadGunTurnRate))
+        }
+
+        SetRadarTurnRate(x) => {
+            state.radar_turn_rate =
+                try!(config.radar_turn_rate_limits.check(x).map_err(BadRadarTurnRate))
+        }
+
+        Shoot(power) => {
+            let power = try!(config.bullet_power_limits.check(power).map_err(BadBulletPower));
+
+            if shot.is_some() {
+                return Err(TooManyBulletsPerFrame);
+            }
+
+            *shot = Some(power);
+        }
+
+        SetColor(color) => {
+            if !valid_color(color) {
+                return Err(BadColor(color));
+            }
+
+            state.color = Some(color);
+        }
+
+        DebugDraw(drawing) => drawings.add(drawing),
+
+        DebugPrint(_) => {}
+    }
+
+    Ok(())
+}
+
+/// Whether a drawing's colour, size and duration make sense.
+pub fn valid_drawing(drawing: &DebugDrawing) -> bool {
+    let valid_size = match drawing.shape {
+        DebugShape::Circle { radius, .. } => radius >= 0.0,
+        _ => true,
+    };
+    let valid_duration = drawing.duration.map_or(true, |d| d >= 0.0);
+
+    valid_color(drawing.color) && valid_size && valid_duration
+}
+
+/// Whether each component of a colour is between 0 and 1.
+pub fn valid_color(color: [f32; 3]) -> bool {
+    color.iter().all(|&c| 0.0 <= c && c <= 1.0)
+}
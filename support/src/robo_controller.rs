@@ -1,3 +1,4 @@
+use actions::{Action, ActionId, Actions};
 use config::*;
 use math::*;
 use rpc::*;
@@ -7,6 +8,7 @@ use serde_json::error::Error as SerdeError;
 
 use std::io;
 use std::io::{BufRead, Write};
+use std::mem;
 use std::ops::Deref;
 
 /// A hook into the robot state
@@ -14,6 +16,7 @@ pub struct RoboHook {
     config: Config,
     state: BotState,
     resps: Vec<Response>,
+    actions: Actions,
 }
 
 #[allow(unused_variables)]
@@ -29,6 +32,10 @@ pub trait RoboController {
     /// Called when an enemy robot is scanned.
     fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {}
 
+    /// Called at the start of a step for each queued action that has finished,
+    /// in the order they were queued.
+    fn action_done(&mut self, hook: &mut RoboHook, id: ActionId, action: Action) {}
+
     /// Called when the robot is about to die, either because it was destroyed
     /// or because the simulation has ended. Note that an _immutable_ reference
     /// to the hook is provided, so the simulation can't be affected in any way
@@ -118,6 +125,118 @@ impl RoboHook {
     pub fn debug_print(&mut self, msg: &str) {
         self.resps.push(Response::DebugPrint(msg.to_owned()));
     }
+
+    /// Set the colour the robot is drawn in. Each component is clamped to
+    /// between 0 and 1.
+    #[inline]
+    pub fn set_color(&mut self, red: f32, green: f32, blue: f32) {
+        let color = clamp_color([red, green, blue]);
+
+        self.state.color = Some(color);
+        self.resps.push(Response::SetColor(color));
+    }
+
+    /// Draw a line over the world until the next step, for debugging.
+    #[inline]
+    pub fn draw_line(&mut self, from: Vector2, to: Vector2, color: [f32; 3]) {
+        self.draw(DebugShape::Line { from: from, to: to }, color, None);
+    }
+
+    /// Draw a circle over the world until the next step, for debugging.
+    #[inline]
+    pub fn draw_circle(&mut self, centre: Vector2, radius: f64, color: [f32; 3]) {
+        self.draw(DebugShape::Circle { centre: centre, radius: radius.abs() }, color, None);
+    }
+
+    /// Mark a point in the world until the next step, for debugging.
+    #[inline]
+    pub fn draw_point(&mut self, pos: Vector2, color: [f32; 3]) {
+        self.draw(DebugShape::Point { pos: pos }, color, None);
+    }
+
+    /// Draw text in the world until the next step, for debugging.
+    #[inline]
+    pub fn draw_text(&mut self, pos: Vector2, text: &str, color: [f32; 3]) {
+        self.draw(DebugShape::Text { pos: pos, text: text.to_owned() }, color, None);
+    }
+
+    /// Draw any debug shape for the given number of seconds, rather than until
+    /// the next step. Negative durations are treated as zero.
+    #[inline]
+    pub fn draw_for(&mut self, shape: DebugShape, color: [f32; 3], duration: f64) {
+        self.draw(shape, color, Some(duration.max(0.0)));
+    }
+
+    /// Queue an action, to be carried out over the coming steps after any
+    /// others queued for the same part of the robot. See the `actions` module.
+    #[inline]
+    pub fn queue(&mut self, action: Action) -> ActionId {
+        self.actions.push(action)
+    }
+
+    /// Queue turning the body to face an absolute heading.
+    #[inline]
+    pub fn turn_to(&mut self, heading: f64) -> ActionId {
+        self.queue(Action::TurnTo(heading))
+    }
+
+    /// Queue driving the given distance, backwards if negative.
+    #[inline]
+    pub fn move_by(&mut self, distance: f64) -> ActionId {
+        self.queue(Action::Move(distance))
+    }
+
+    /// Queue turning the gun to face an absolute bearing.
+    #[inline]
+    pub fn aim_gun(&mut self, bearing: f64) -> ActionId {
+        self.queue(Action::AimGun(bearing))
+    }
+
+    /// Queue turning the radar to face a position.
+    #[inline]
+    pub fn point_radar_at(&mut self, pos: Vector2) -> ActionId {
+        self.queue(Action::PointRadarAt(pos))
+    }
+
+    /// Drop every queued action, stopping the parts that were busy.
+    #[inline]
+    pub fn clear_actions(&mut self) {
+        self.actions.clear()
+    }
+
+    /// The number of actions queued, including those under way.
+    #[inline]
+    pub fn queued_actions(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Set the rates that carry out the actions under way, given the state at
+    /// the start of the step. The rates the controller set itself during the
+    /// step are in the hook's state.
+    fn carry_out_actions(&mut self, start: &BotState) {
+        let commands = self.actions.commands(&self.config, start, &self.state);
+
+        if let Some(turn_rate) = commands.turn_rate {
+            self.set_turn_rate(turn_rate);
+        }
+        if let Some(thrust) = commands.thrust {
+            self.set_thrust(thrust);
+        }
+        if let Some(gun_turn_rate) = commands.gun_turn_rate {
+            self.set_gun_turn_rate(gun_turn_rate);
+        }
+        if let Some(radar_turn_rate) = commands.radar_turn_rate {
+            self.set_radar_turn_rate(radar_turn_rate);
+        }
+    }
+
+    fn draw(&mut self, shape: DebugShape, color: [f32; 3], duration: Option<f64>) {
+        self.resps.push(Response::DebugDraw(DebugDrawing {
+            shape: shape,
+            color: clamp_color(color),
+            duration: duration,
+        }));
+    }
 }
 
 impl Deref for RoboHook {
@@ -128,6 +247,113 @@ impl Deref for RoboHook {
     }
 }
 
+fn clamp_color(color: [f32; 3]) -> [f32; 3] {
+    [color[0].max(0.0).min(1.0), color[1].max(0.0).min(1.0), color[2].max(0.0).min(1.0)]
+}
+
+impl<C: RoboController + ?Sized> RoboController for Box<C> {
+    fn init(&mut self, hook: &mut RoboHook) {
+        (**self).init(hook)
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, elapsed: f64) {
+        (**self).step(hook, elapsed)
+    }
+
+    fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {
+        (**self).scan(hook, scan_pos)
+    }
+
+    fn action_done(&mut self, hook: &mut RoboHook, id: ActionId, action: Action) {
+        (**self).action_done(hook, id, action)
+    }
+
+    fn kill(&mut self, hook: &RoboHook) {
+        (**self).kill(hook)
+    }
+}
+
+impl<'a, C: RoboController + ?Sized> RoboController for &'a mut C {
+    fn init(&mut self, hook: &mut RoboHook) {
+        (**self).init(hook)
+    }
+
+    fn step(&mut self, hook: &mut RoboHook, elapsed: f64) {
+        (**self).step(hook, elapsed)
+    }
+
+    fn scan(&mut self, hook: &mut RoboHook, scan_pos: Vector2) {
+        (**self).scan(hook, scan_pos)
+    }
+
+    fn action_done(&mut self, hook: &mut RoboHook, id: ActionId, action: Action) {
+        (**self).action_done(hook, id, action)
+    }
+
+    fn kill(&mut self, hook: &RoboHook) {
+        (**self).kill(hook)
+    }
+}
+
+/// Drives a controller from the simulator's messages, keeping track of the
+/// configuration it was initialised with. This is what `run` does over stdio,
+/// but it can also be used to host a controller directly in the simulator.
+pub struct Host<C> {
+    controller: C,
+    config: Config,
+    actions: Actions,
+}
+
+impl<C: RoboController> Host<C> {
+    pub fn new(controller: C) -> Self {
+        Host {
+            controller: controller,
+            config: Config::default(),
+            actions: Actions::default(),
+        }
+    }
+
+    /// Pass a message to the controller, returning the responses it made.
+    pub fn respond(&mut self, state: BotState, msg: Message) -> Vec<Response> {
+        let mut hook = RoboHook {
+            config: self.config.clone(),
+            state: state,
+            resps: Vec::new(),
+            actions: mem::replace(&mut self.actions, Actions::default()),
+        };
+
+        use rpc::Message::*;
+
+        match msg {
+            Init { config } => {
+                self.config = config;
+                hook.config = self.config.clone();
+                self.controller.init(&mut hook)
+            },
+            Step { elapsed } => {
+                let start = hook.state.clone();
+                for (id, action) in hook.actions.update(&start) {
+                    self.controller.action_done(&mut hook, id, action);
+                }
+
+                self.controller.step(&mut hook, elapsed);
+                hook.carry_out_actions(&start);
+            },
+            Scan { scan_pos } => self.controller.scan(&mut hook, scan_pos),
+            Kill => self.controller.kill(&hook),
+        }
+
+        self.actions = hook.actions;
+        hook.resps
+    }
+
+    #[inline]
+    pub fn controller(&self) -> &C { &self.controller }
+
+    #[inline]
+    pub fn controller_mut(&mut self) -> &mut C { &mut self.controller }
+}
+
 pub fn run<Ctl: RoboController>(ctl: &mut Ctl) -> Result<(), Error> {
     let stdin = io::stdin();
     let stdout = io::stdout();
@@ -135,7 +361,7 @@ pub fn run<Ctl: RoboController>(ctl: &mut Ctl) -> Result<(), Error> {
     let mut stdin = stdin.lock();
     let mut stdout = stdout.lock();
 
-    let mut config = Config::default();
+    let mut host = Host::new(ctl);
 
     let mut alive = true;
 
@@ -145,27 +371,14 @@ pub fn run<Ctl: RoboController>(ctl: &mut Ctl) -> Result<(), Error> {
         try!(stdin.read_line(&mut buf).map_err(Error::Read));
         let (state, msg): (BotState, Message) = try!(serde_json::from_str(buf.as_str()).map_err(Error::Deserialization));
 
-        let mut hook = RoboHook {
-            config: config.clone(),
-            state: state,
-            resps: Vec::new(),
-        };
-
-        use rpc::Message::*;
-
-        match msg {
-            Init { config: new_config } => {
-                config = new_config;
-                hook.config = config.clone();
-                ctl.init(&mut hook)
-            },
-            Step { elapsed } => ctl.step(&mut hook, elapsed),
-            Scan { scan_pos } => ctl.scan(&mut hook, scan_pos),
-            Kill => alive = false,
+        if let Message::Kill = msg {
+            alive = false;
         }
 
+        let resps = host.respond(state, msg);
+
         // Write responses
-        let resp = try!(serde_json::to_string(&hook.resps).map_err(Error::Serialization));
+        let resp = try!(serde_json::to_string(&resps).map_err(Error::Serialization));
         try!(writeln!(stdout, "{}", resp).map_err(Error::Write));
     }
 
@@ -1,7 +1,7 @@
 use math::*;
 use config::*;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct BotState {
     /// Position, with the origin in the lower left.
     pub pos: Vector2,
@@ -37,7 +37,49 @@ pub struct BotState {
     pub hit_points: f64,
 
     /// Shooting requires shoot power. It regenerates over time.
-    pub shoot_power: f64
+    pub shoot_power: f64,
+
+    /// The colour the robot asked to be drawn in, as red, green and blue
+    /// between 0 and 1. If not set, the viewer picks one.
+    pub color: Option<[f32; 3]>,
+}
+
+/// A shape which a robot can draw over the world to help debug it. Positions
+/// are in world coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DebugShape {
+    Line {
+        from: Vector2,
+        to: Vector2,
+    },
+
+    Circle {
+        centre: Vector2,
+        radius: f64,
+    },
+
+    Point {
+        pos: Vector2,
+    },
+
+    /// Text with its top left corner at the given position.
+    Text {
+        pos: Vector2,
+        text: String,
+    },
+}
+
+/// A debug shape, and how to draw it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebugDrawing {
+    pub shape: DebugShape,
+
+    /// The colour to draw the shape in, as red, green and blue between 0 and 1.
+    pub color: [f32; 3],
+
+    /// How long the shape is drawn for, in seconds. If `None`, it is drawn
+    /// until the robot's next step.
+    pub duration: Option<f64>,
 }
 
 /// A message which can be sent to the child process.
@@ -67,7 +109,7 @@ pub enum Message {
 }
 
 /// A response which can be sent back to the robot to control it.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Response {
     /// Set the thrust. Causes an error if the value is not within the range
     /// specified by `thrust_limits` in the configuration.
@@ -91,4 +133,14 @@ pub enum Response {
 
     /// Print a message to the simulation console.
     DebugPrint(String),
+
+    /// Set the colour the robot is drawn in, as red, green and blue. Causes an
+    /// error if any component is not between 0 and 1. Usually sent in response
+    /// to `Init`.
+    SetColor([f32; 3]),
+
+    /// Draw a shape over the world, for debugging. The shape is dropped, and
+    /// counted in the robot's diagnostics, if its colour, duration or size is
+    /// invalid, or if the robot would have too many shapes at once.
+    DebugDraw(DebugDrawing),
 }
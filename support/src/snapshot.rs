@@ -0,0 +1,146 @@
+use math::*;
+use rpc::*;
+
+/// A bullet in flight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bullet {
+    /// The index in the world of the robot which fired the bullet.
+    pub owner: usize,
+
+    /// Position, with the origin in the lower left.
+    pub pos: Vector2,
+
+    /// The velocity, in units per second.
+    pub vel: Vector2,
+
+    /// The power the bullet was fired with. This is also the damage it does
+    /// when it hits a robot.
+    pub power: f64,
+}
+
+/// Something notable that happened in the world during a tick. Robots are
+/// referred to by their index in the world.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// A robot fired a bullet.
+    Shot {
+        bot: usize,
+        power: f64,
+    },
+
+    /// A robot was hit by another robot's bullet.
+    Hit {
+        bot: usize,
+        shooter: usize,
+        damage: f64,
+    },
+
+    /// A robot's radar swept over another robot.
+    Scan {
+        bot: usize,
+        target: usize,
+    },
+
+    /// A robot's hit points reached zero.
+    Death {
+        bot: usize,
+    },
+
+    /// A robot printed a debug message.
+    DebugPrint {
+        bot: usize,
+        msg: String,
+    },
+
+    /// A robot sent responses for a step.
+    Responded {
+        bot: usize,
+        responses: Vec<Response>,
+    },
+
+    /// A robot was given a strike for misbehaving.
+    Strike {
+        bot: usize,
+        error: String,
+    },
+
+    /// A robot got too many strikes, and was removed from the match.
+    Disqualified {
+        bot: usize,
+    },
+}
+
+/// How well a robot's controller is communicating with it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    /// The time the robot took to respond to its last step, in seconds. This
+    /// depends on the machine, so it differs between runs of the same match.
+    pub latency: f64,
+
+    /// The number of errors the robot has caused, such as invalid responses.
+    pub errors: u32,
+
+    /// The number of steps in which the robot sent invalid responses.
+    pub strikes: u32,
+
+    /// The most recent error, if any.
+    pub last_error: Option<String>,
+
+    /// The number of debug drawings dropped for being invalid, or for going
+    /// over the limit on how many a robot can have at once.
+    pub dropped_drawings: u32,
+
+    /// Whether the robot was removed from the match for getting too many
+    /// strikes.
+    pub disqualified: bool,
+}
+
+/// A debug shape being drawn by a robot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Drawing {
+    /// The index in the world of the robot which drew the shape.
+    pub bot: usize,
+
+    pub shape: DebugShape,
+
+    /// The colour to draw the shape in, as red, green and blue between 0 and 1.
+    pub color: [f32; 3],
+}
+
+/// Running totals for a robot over the match so far.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    /// The number of robots this robot has destroyed.
+    pub kills: u32,
+
+    /// The total damage done by this robot's bullets.
+    pub damage_dealt: f64,
+
+    /// The total damage done to this robot.
+    pub damage_taken: f64,
+}
+
+/// The public state of the world at the end of a single tick.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The number of ticks since the start of the simulation.
+    pub tick: u64,
+
+    /// The state of each robot, in world order.
+    pub bots: Vec<BotState>,
+
+    /// Every bullet currently in flight.
+    pub bullets: Vec<Bullet>,
+
+    /// Everything that happened during this tick.
+    pub events: Vec<Event>,
+
+    /// Each robot's score so far, in world order.
+    pub scores: Vec<Score>,
+
+    /// Every debug shape being drawn by the robots during this tick.
+    pub drawings: Vec<Drawing>,
+
+    /// Each robot's diagnostics, in world order.
+    pub diagnostics: Vec<Diagnostics>,
+}
@@ -0,0 +1,388 @@
+//! Geometry for aiming at other robots: angle helpers, intercept solvers for
+//! targets moving in straight lines or circles, guess factors, and how much
+//! power to shoot with.
+//!
+//! Angles are in radians, going anticlockwise with 0 pointing right, as in
+//! `BotState`.
+
+use config::Config;
+use math::*;
+use physics;
+use rpc::BotState;
+
+use std::f64::consts::PI;
+
+/// Wrap an angle to between -π and π.
+pub fn normalize_angle(angle: f64) -> f64 {
+    let wrapped = (angle + PI) % (2.0 * PI);
+
+    if wrapped < 0.0 {
+        wrapped + PI
+    } else {
+        wrapped - PI
+    }
+}
+
+/// Wrap an angle to between 0 and 2π.
+pub fn normalize_positive_angle(angle: f64) -> f64 {
+    let wrapped = angle % (2.0 * PI);
+
+    if wrapped < 0.0 {
+        wrapped + 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+/// The shortest turn from one angle to another, positive if anticlockwise.
+pub fn angle_between(from: f64, to: f64) -> f64 {
+    normalize_angle(to - from)
+}
+
+/// The direction a vector points in.
+pub fn angle_of(v: Vector2) -> f64 {
+    v.y.atan2(v.x)
+}
+
+/// The direction from one point to another.
+pub fn bearing(from: Vector2, to: Vector2) -> f64 {
+    angle_of(to - from)
+}
+
+/// A robot's velocity, from its heading and speed.
+pub fn velocity(state: &BotState) -> Vector2 {
+    Vector2::new(state.heading.cos(), state.heading.sin()) * state.speed
+}
+
+/// The fastest a robot can drive, once friction balances full thrust.
+pub fn max_speed(config: &Config) -> f64 {
+    let thrust = config.thrust_limits.max.max(-config.thrust_limits.min);
+    let friction = config.drive_friction;
+
+    if friction < 1.0 {
+        thrust * config.tick_seconds() * friction / (1.0 - friction)
+    } else {
+        f64::INFINITY
+    }
+}
+
+/// Where a robot will be after the given number of ticks if its rates don't
+/// change. Rates set in response to a step only take effect at the next step,
+/// so this is where to aim from when choosing them.
+pub fn predict(config: &Config, state: &BotState, ticks: u32) -> BotState {
+    let mut state = state.clone();
+
+    for _ in 0..ticks {
+        physics::advance(config, &mut state, config.tick_seconds());
+    }
+
+    state
+}
+
+/// Where to aim from `shooter` to hit a target that keeps going in a straight
+/// line, or `None` if a bullet can't catch it.
+pub fn linear_intercept(shooter: Vector2,
+                        target_pos: Vector2,
+                        target_vel: Vector2,
+                        bullet_speed: f64)
+                        -> Option<Vector2> {
+    let offset = target_pos - shooter;
+    let vel = target_vel;
+
+    // Solve |offset + vel * t| = bullet_speed * t for the first t >= 0.
+    let a = vel.x * vel.x + vel.y * vel.y - bullet_speed * bullet_speed;
+    let b = 2.0 * (offset.x * vel.x + offset.y * vel.y);
+    let c = offset.x * offset.x + offset.y * offset.y;
+
+    let t = if a.abs() < 1e-12 {
+        // The target is as fast as the bullet, so there's only one root.
+        if b >= 0.0 {
+            return None;
+        }
+
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let root = discriminant.sqrt();
+        let t1 = (-b - root) / (2.0 * a);
+        let t2 = (-b + root) / (2.0 * a);
+        let (first, second) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+
+        if first >= 0.0 { first } else { second }
+    };
+
+    if t >= 0.0 {
+        Some(target_pos + vel * t)
+    } else {
+        None
+    }
+}
+
+/// Where to aim from `shooter` to hit a target that keeps turning at the same
+/// rate and going at the same speed, found by stepping it forwards by
+/// `time_step` seconds until a bullet could reach it. Gives `None` if it can't
+/// within `max_time` seconds.
+pub fn circular_intercept(shooter: Vector2,
+                          target_pos: Vector2,
+                          target_vel: Vector2,
+                          target_turn_rate: f64,
+                          bullet_speed: f64,
+                          time_step: f64,
+                          max_time: f64)
+                          -> Option<Vector2> {
+    let speed = target_vel.length();
+    let mut heading = angle_of(target_vel);
+    let mut pos = target_pos;
+    let mut t = 0.0;
+
+    while t <= max_time {
+        if (pos - shooter).length() <= bullet_speed * t {
+            return Some(pos);
+        }
+
+        heading += target_turn_rate * time_step;
+        pos += Vector2::new(heading.cos(), heading.sin()) * (speed * time_step);
+        t += time_step;
+    }
+
+    None
+}
+
+/// The widest angle, either side of the direct bearing, a target at the given
+/// speed could get to before a bullet reaches it.
+pub fn max_escape_angle(bullet_speed: f64, target_speed: f64) -> f64 {
+    (target_speed / bullet_speed).min(1.0).asin()
+}
+
+/// Which way a target is moving across the shooter's view: 1 if anticlockwise,
+/// -1 if clockwise. Targets moving straight towards or away count as
+/// anticlockwise.
+pub fn lateral_direction(shooter: Vector2, target_pos: Vector2, target_vel: Vector2) -> f64 {
+    let offset = target_pos - shooter;
+    let lateral = offset.x * target_vel.y - offset.y * target_vel.x;
+
+    if lateral < 0.0 { -1.0 } else { 1.0 }
+}
+
+/// The guess factor of a hit: how far round the target got, between -1 and 1,
+/// as a fraction of its maximum escape angle in the direction it was moving
+/// when the shot was fired. Hits recorded this way say where to aim next time,
+/// whatever the target's direction or distance.
+pub fn guess_factor(shooter: Vector2,
+                    target_pos: Vector2,
+                    direction: f64,
+                    hit_pos: Vector2,
+                    max_escape: f64)
+                    -> f64 {
+    if max_escape <= 0.0 {
+        return 0.0;
+    }
+
+    let offset = angle_between(bearing(shooter, target_pos), bearing(shooter, hit_pos));
+    (offset * direction / max_escape).max(-1.0).min(1.0)
+}
+
+/// The bearing to fire at for a guess factor, the reverse of `guess_factor`.
+pub fn guess_factor_bearing(shooter: Vector2,
+                            target_pos: Vector2,
+                            direction: f64,
+                            factor: f64,
+                            max_escape: f64)
+                            -> f64 {
+    let factor = factor.max(-1.0).min(1.0);
+    normalize_angle(bearing(shooter, target_pos) + factor * direction * max_escape)
+}
+
+/// How much power to shoot at a target at the given distance with, or `None`
+/// if there isn't enough shoot power for the weakest shot. Close targets are
+/// worth full power, and it tails off to the least power at the edge of the
+/// radar's range. Shots are limited to the power the shooter has left.
+pub fn recommended_power(config: &Config, shooter: &BotState, distance: f64) -> Option<f64> {
+    let limits = &config.bullet_power_limits;
+
+    if shooter.shoot_power < limits.min {
+        return None;
+    }
+
+    let closeness = if config.radar_range > 0.0 {
+        1.0 - (distance / config.radar_range).max(0.0).min(1.0)
+    } else {
+        1.0
+    };
+
+    let power = limits.min + (limits.max - limits.min) * closeness;
+    Some(power.min(shooter.shoot_power))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Config;
+    use math::*;
+    use rpc::BotState;
+
+    use std::f64::consts::PI;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn normalizes_angles() {
+        assert!(close(normalize_angle(0.0), 0.0));
+        assert!(close(normalize_angle(3.0 * PI / 2.0), -PI / 2.0));
+        assert!(close(normalize_angle(-3.0 * PI / 2.0), PI / 2.0));
+        assert!(close(normalize_angle(5.0 * PI), -PI) || close(normalize_angle(5.0 * PI), PI));
+
+        assert!(close(normalize_positive_angle(-PI / 2.0), 3.0 * PI / 2.0));
+        assert!(close(normalize_positive_angle(9.0 * PI / 2.0), PI / 2.0));
+
+        assert!(close(angle_between(3.0, -3.0), 2.0 * PI - 6.0));
+        assert!(close(angle_between(-3.0, 3.0), 6.0 - 2.0 * PI));
+
+        for i in -100..100 {
+            let angle = i as f64 * 0.37;
+            let normalized = normalize_angle(angle);
+            assert!(-PI - EPSILON <= normalized && normalized <= PI + EPSILON);
+            assert!(close(normalized.sin(), angle.sin()) && close(normalized.cos(), angle.cos()));
+        }
+    }
+
+    #[test]
+    fn linear_intercept_of_a_still_target() {
+        let target = Vector2::new(10.0, 5.0);
+        let aim = linear_intercept(Vector2::zero(), target, Vector2::zero(), 50.0);
+        assert_eq!(aim, Some(target));
+    }
+
+    #[test]
+    fn linear_intercept_meets_the_target() {
+        let shooter = Vector2::new(1.0, 2.0);
+        let target = Vector2::new(20.0, -4.0);
+        let vel = Vector2::new(-3.0, 2.5);
+        let bullet_speed = 10.0;
+
+        let aim = linear_intercept(shooter, target, vel, bullet_speed).unwrap();
+
+        // The bullet and target arrive at the same time.
+        let target_time = (aim - target).length() / vel.length();
+        let bullet_time = (aim - shooter).length() / bullet_speed;
+        assert!(close(target_time, bullet_time));
+
+        // And the target goes through the aim point.
+        let along = target + vel * target_time;
+        assert!(close(along.x, aim.x) && close(along.y, aim.y));
+    }
+
+    #[test]
+    fn linear_intercept_of_an_escaping_target() {
+        let aim = linear_intercept(Vector2::zero(),
+                                   Vector2::new(10.0, 0.0),
+                                   Vector2::new(60.0, 0.0),
+                                   50.0);
+        assert_eq!(aim, None);
+
+        let aim = linear_intercept(Vector2::zero(),
+                                   Vector2::new(10.0, 0.0),
+                                   Vector2::new(50.0, 0.0),
+                                   50.0);
+        assert_eq!(aim, None);
+    }
+
+    #[test]
+    fn circular_intercept_without_turning_is_linear() {
+        let shooter = Vector2::zero();
+        let target = Vector2::new(15.0, 10.0);
+        let vel = Vector2::new(0.0, -3.0);
+
+        let linear = linear_intercept(shooter, target, vel, 20.0).unwrap();
+        let circular = circular_intercept(shooter, target, vel, 0.0, 20.0, 1e-4, 5.0).unwrap();
+
+        assert!((linear - circular).length() < 1e-2);
+    }
+
+    #[test]
+    fn circular_intercept_stays_on_the_circle() {
+        // A target going round a circle of radius 5 about (20, 0).
+        let centre = Vector2::new(20.0, 0.0);
+        let target = Vector2::new(25.0, 0.0);
+        let vel = Vector2::new(0.0, 5.0);
+
+        let aim = circular_intercept(Vector2::zero(), target, vel, 1.0, 10.0, 1e-3, 10.0).unwrap();
+
+        assert!(((aim - centre).length() - 5.0).abs() < 1e-2);
+        assert_eq!(circular_intercept(Vector2::zero(), target, vel, 1.0, 10.0, 1e-3, 0.1), None);
+    }
+
+    #[test]
+    fn guess_factors_round_trip() {
+        let shooter = Vector2::new(5.0, 5.0);
+        let target = Vector2::new(25.0, 5.0);
+        let max_escape = max_escape_angle(50.0, 3.0);
+
+        assert_eq!(lateral_direction(shooter, target, Vector2::new(0.0, 1.0)), 1.0);
+        assert_eq!(lateral_direction(shooter, target, Vector2::new(0.0, -1.0)), -1.0);
+
+        for &direction in &[1.0, -1.0] {
+            for i in -4..5 {
+                let factor = i as f64 / 4.0;
+                let bearing = guess_factor_bearing(shooter, target, direction, factor, max_escape);
+                let hit = shooter + Vector2::new(bearing.cos(), bearing.sin()) * 20.0;
+
+                assert!(close(guess_factor(shooter, target, direction, hit, max_escape), factor));
+            }
+        }
+
+        // Hits beyond the escape angle are clamped.
+        let wide = shooter + Vector2::new(0.0, 20.0);
+        assert_eq!(guess_factor(shooter, target, 1.0, wide, max_escape), 1.0);
+    }
+
+    #[test]
+    fn power_depends_on_distance_and_shoot_power() {
+        let config = Config::default();
+        let limits = config.bullet_power_limits.clone();
+        let mut state = BotState {
+            shoot_power: config.max_shoot_power,
+            ..BotState::default()
+        };
+
+        assert_eq!(recommended_power(&config, &state, 0.0), Some(limits.max));
+        assert_eq!(recommended_power(&config, &state, config.radar_range * 2.0), Some(limits.min));
+
+        let near = recommended_power(&config, &state, 5.0).unwrap();
+        let far = recommended_power(&config, &state, 30.0).unwrap();
+        assert!(near > far);
+
+        state.shoot_power = (limits.min + limits.max) / 2.0;
+        assert_eq!(recommended_power(&config, &state, 0.0), Some(state.shoot_power));
+
+        state.shoot_power = limits.min / 2.0;
+        assert_eq!(recommended_power(&config, &state, 0.0), None);
+    }
+
+    #[test]
+    fn predicts_with_the_same_physics() {
+        let config = Config::default();
+        let state = BotState {
+            speed: 2.0,
+            turn_rate: 1.0,
+            gun_turn_rate: -0.5,
+            ..BotState::default()
+        };
+
+        let next = predict(&config, &state, config.ticks_per_step);
+        let step_seconds = config.step_seconds();
+
+        assert!(close(next.heading, step_seconds));
+        assert!(close(next.gun_heading, 0.5 * step_seconds));
+        assert!(next.pos.length() > 0.0);
+        assert!(max_speed(&config) > 0.0);
+    }
+}